@@ -0,0 +1,155 @@
+//! Conversions between public keys and their JSON Web Key (RFC 7517) members.
+
+use openssl::base64;
+use openssl::bn::{BigNum, BigNumContext, BigNumRef};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Public};
+use openssl::rsa::Rsa;
+use serde_json::{Map, Value};
+
+/// Why a JWK could not be converted, for the caller to attach to its own error.
+pub(crate) type JwkResult<T> = std::result::Result<T, String>;
+
+/// JWK curve names, with the length in bytes of their fixed-size coordinates.
+const CURVES: &[(&str, Nid, usize)] = &[
+    ("P-256", Nid::X9_62_PRIME256V1, 32),
+    ("P-384", Nid::SECP384R1, 48),
+    ("P-521", Nid::SECP521R1, 66),
+];
+
+/// Reads the public key from the `kty` specific members of `jwk`. EC keys on the NIST curves and
+/// RSA keys are supported.
+pub(crate) fn public_key_from_jwk(jwk: &Map<String, Value>) -> JwkResult<PKey<Public>> {
+    match string_member(jwk, "kty")? {
+        "EC" => {
+            let crv = string_member(jwk, "crv")?;
+            let nid = CURVES
+                .iter()
+                .find(|(name, _, _)| *name == crv)
+                .map(|(_, nid, _)| *nid)
+                .ok_or_else(|| format!("unsupported curve {}", crv))?;
+            let x = bignum_member(jwk, "x")?;
+            let y = bignum_member(jwk, "y")?;
+
+            EcGroup::from_curve_name(nid)
+                .and_then(|group| EcKey::from_public_key_affine_coordinates(&group, &x, &y))
+                .and_then(PKey::from_ec_key)
+                .map_err(|e| format!("invalid EC key: {}", e))
+        }
+        "RSA" => {
+            let n = bignum_member(jwk, "n")?;
+            let e = bignum_member(jwk, "e")?;
+
+            Rsa::from_public_components(n, e)
+                .and_then(PKey::from_rsa)
+                .map_err(|e| format!("invalid RSA key: {}", e))
+        }
+        kty => Err(format!("unsupported key type {}", kty)),
+    }
+}
+
+/// Writes the `kty` specific members for `key`.
+pub(crate) fn public_key_to_jwk<T: HasPublic>(
+    key: &PKeyRef<T>,
+    jwk: &mut Map<String, Value>,
+) -> JwkResult<()> {
+    let ssl_error = |e: ErrorStack| e.to_string();
+
+    match key.id() {
+        Id::EC => {
+            let ec = key.ec_key().map_err(ssl_error)?;
+            let group = ec.group();
+            let (crv, _, len) = group
+                .curve_name()
+                .and_then(|nid| CURVES.iter().find(|(_, n, _)| *n == nid))
+                .ok_or_else(|| "unsupported curve".to_string())?;
+
+            let mut x = BigNum::new().map_err(ssl_error)?;
+            let mut y = BigNum::new().map_err(ssl_error)?;
+            let mut ctx = BigNumContext::new().map_err(ssl_error)?;
+            ec.public_key()
+                .affine_coordinates_gfp(group, &mut x, &mut y, &mut ctx)
+                .map_err(ssl_error)?;
+
+            jwk.insert("kty".to_string(), "EC".into());
+            jwk.insert("crv".to_string(), (*crv).into());
+            jwk.insert("x".to_string(), base64url_encode(&padded(&x, *len)).into());
+            jwk.insert("y".to_string(), base64url_encode(&padded(&y, *len)).into());
+            Ok(())
+        }
+        Id::RSA => {
+            let rsa = key.rsa().map_err(ssl_error)?;
+            jwk.insert("kty".to_string(), "RSA".into());
+            jwk.insert("n".to_string(), base64url_encode(&rsa.n().to_vec()).into());
+            jwk.insert("e".to_string(), base64url_encode(&rsa.e().to_vec()).into());
+            Ok(())
+        }
+        _ => Err("unsupported key type".to_string()),
+    }
+}
+
+pub(crate) fn string_member<'a>(jwk: &'a Map<String, Value>, name: &str) -> JwkResult<&'a str> {
+    jwk.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("{} is missing or not a string", name))
+}
+
+fn bignum_member(jwk: &Map<String, Value>, name: &str) -> JwkResult<BigNum> {
+    let bytes = base64url_decode(string_member(jwk, name)?)
+        .ok_or_else(|| format!("{} is not valid base64url", name))?;
+    BigNum::from_slice(&bytes).map_err(|e| e.to_string())
+}
+
+/// The big-endian bytes of `n`, left-padded with zeros to `len` bytes.
+pub(crate) fn padded(n: &BigNumRef, len: usize) -> Vec<u8> {
+    let bytes = n.to_vec();
+    let mut out = vec![0; len.saturating_sub(bytes.len())];
+    out.extend(bytes);
+    out
+}
+
+/// Base64url without padding (RFC 7515, section 2).
+pub(crate) fn base64url_encode(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+
+    base64::encode_block(bytes)
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Decodes base64url, with or without padding. Returns `None` if `encoded` is not base64url.
+pub(crate) fn base64url_decode(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.is_empty() {
+        return Some(Vec::new());
+    }
+    if encoded.contains(&['+', '/'][..]) {
+        return None;
+    }
+
+    let mut standard: String = encoded
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    match standard.len() % 4 {
+        1 => return None,
+        n if n > 0 => standard.push_str(&"=="[..4 - n]),
+        _ => {}
+    }
+
+    base64::decode_block(&standard).ok()
+}
@@ -0,0 +1,121 @@
+use crate::uri::{TrustDomain, URI};
+use openssl::pkey::{PKey, Public};
+use std::collections::hash_map::{HashMap, Values};
+use std::fmt;
+
+/// The JWT authorities of a single trust domain, keyed by key ID, used to validate JWT-SVIDs
+/// issued in that trust domain.
+#[derive(Clone)]
+pub struct JwtBundle {
+    trust_domain: TrustDomain,
+    authorities: HashMap<String, PKey<Public>>,
+}
+
+impl JwtBundle {
+    pub fn new(trust_domain: TrustDomain) -> JwtBundle {
+        JwtBundle {
+            trust_domain,
+            authorities: HashMap::new(),
+        }
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn authorities(&self) -> &HashMap<String, PKey<Public>> {
+        &self.authorities
+    }
+
+    pub fn find_authority(&self, key_id: &str) -> Option<&PKey<Public>> {
+        self.authorities.get(key_id)
+    }
+
+    /// Adds an authority, returning the one it replaces under the same key ID, if any.
+    pub fn add_authority(&mut self, key_id: &str, authority: PKey<Public>) -> Option<PKey<Public>> {
+        self.authorities.insert(key_id.to_string(), authority)
+    }
+
+    pub fn remove_authority(&mut self, key_id: &str) -> Option<PKey<Public>> {
+        self.authorities.remove(key_id)
+    }
+
+    pub fn contains_authority(&self, key_id: &str) -> bool {
+        self.authorities.contains_key(key_id)
+    }
+}
+
+/// JWT bundles keyed by trust domain, e.g. a workload's own bundle and the bundles of the trust
+/// domains it federates with.
+#[derive(Clone, Debug, Default)]
+pub struct JwtBundleSet {
+    bundles: HashMap<TrustDomain, JwtBundle>,
+}
+
+impl JwtBundleSet {
+    pub fn new() -> JwtBundleSet {
+        JwtBundleSet::default()
+    }
+
+    /// Inserts a bundle, returning the bundle it replaces for the same trust domain, if any.
+    pub fn insert(&mut self, bundle: JwtBundle) -> Option<JwtBundle> {
+        self.bundles.insert(bundle.trust_domain().clone(), bundle)
+    }
+
+    pub fn remove(&mut self, trust_domain: &TrustDomain) -> Option<JwtBundle> {
+        self.bundles.remove(trust_domain)
+    }
+
+    pub fn get(&self, trust_domain: &TrustDomain) -> Option<&JwtBundle> {
+        self.bundles.get(trust_domain)
+    }
+
+    /// The bundle to validate JWT-SVIDs for `id` against, i.e. the bundle of its trust domain.
+    pub fn get_for_id(&self, id: &URI) -> Option<&JwtBundle> {
+        self.get(id.trust_domain())
+    }
+
+    pub fn contains(&self, trust_domain: &TrustDomain) -> bool {
+        self.bundles.contains_key(trust_domain)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    /// The bundles in no particular order.
+    pub fn iter(&self) -> Values<'_, TrustDomain, JwtBundle> {
+        self.bundles.values()
+    }
+
+    /// The trust domains of the bundles, sorted by name.
+    pub fn trust_domains(&self) -> Vec<TrustDomain> {
+        let mut trust_domains: Vec<TrustDomain> = self.bundles.keys().cloned().collect();
+        trust_domains.sort();
+        trust_domains
+    }
+}
+
+impl<'a> IntoIterator for &'a JwtBundleSet {
+    type Item = &'a JwtBundle;
+    type IntoIter = Values<'a, TrustDomain, JwtBundle>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for JwtBundle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut key_ids: Vec<&String> = self.authorities.keys().collect();
+        key_ids.sort();
+        f.debug_struct("JwtBundle")
+            .field("trust_domain", &self.trust_domain)
+            .field("authorities", &key_ids)
+            .finish()
+    }
+}
@@ -0,0 +1,37 @@
+pub(crate) mod jwk;
+pub mod jwt;
+pub mod spiffe;
+pub mod x509;
+
+use crate::svid;
+use crate::uri;
+use error_chain::error_chain;
+
+error_chain! {
+    errors {
+        InvalidBundle(trust_domain: String) {
+            description("An error during the parsing of a trust bundle")
+            display("Unable to parse bundle for trust domain {}", trust_domain)
+        }
+
+        InvalidSpiffeBundle(trust_domain: String, reason: String) {
+            description("An error during the parsing of a SPIFFE bundle document")
+            display("Invalid SPIFFE bundle for trust domain {}: {}", trust_domain, reason)
+        }
+
+        TrustDomainMismatch(first: String, second: String) {
+            description("An error during the combination of trust bundles")
+            display("Unable to combine bundles of trust domains {} and {}", first, second)
+        }
+    }
+
+    links {
+        Uri(uri::Error, uri::ErrorKind);
+        X509SVID(svid::x509::Error, svid::x509::ErrorKind);
+    }
+
+    foreign_links {
+        SSL(::openssl::error::ErrorStack);
+        Json(::serde_json::Error);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::bundle::jwk::{self, public_key_from_jwk, public_key_to_jwk, string_member};
+use crate::bundle::jwt::JwtBundle;
+use crate::bundle::x509::X509Bundle;
+use crate::bundle::{Error, ErrorKind, Result};
+use crate::uri::TrustDomain;
+use openssl::base64;
+use serde_json::{Map, Value};
+use std::time::Duration;
+
+const X509_SVID_USE: &str = "x509-svid";
+const JWT_SVID_USE: &str = "jwt-svid";
+
+/// A trust domain's bundle in the SPIFFE bundle format: a JWK Set whose keys are X.509 authorities
+/// (`"use": "x509-svid"`) or JWT authorities (`"use": "jwt-svid"`), along with the
+/// `spiffe_sequence` and `spiffe_refresh_hint` members.
+#[derive(Clone, Debug)]
+pub struct SpiffeBundle {
+    x509: X509Bundle,
+    jwt: JwtBundle,
+    sequence: Option<u64>,
+    refresh_hint: Option<Duration>,
+}
+
+impl SpiffeBundle {
+    pub fn new(trust_domain: TrustDomain) -> SpiffeBundle {
+        SpiffeBundle {
+            x509: X509Bundle::new(trust_domain.clone()),
+            jwt: JwtBundle::new(trust_domain),
+            sequence: None,
+            refresh_hint: None,
+        }
+    }
+
+    /// Combines the X.509 and JWT bundles of a trust domain.
+    pub fn from_bundles(x509: X509Bundle, jwt: JwtBundle) -> Result<SpiffeBundle> {
+        if x509.trust_domain() != jwt.trust_domain() {
+            return Err(ErrorKind::TrustDomainMismatch(
+                x509.trust_domain().to_string(),
+                jwt.trust_domain().to_string(),
+            )
+            .into());
+        }
+
+        Ok(SpiffeBundle {
+            x509,
+            jwt,
+            sequence: None,
+            refresh_hint: None,
+        })
+    }
+
+    /// Parses a bundle document. Keys with a `use` other than `x509-svid` or `jwt-svid` are
+    /// ignored, as the format requires.
+    pub fn parse(trust_domain: TrustDomain, json: &[u8]) -> Result<SpiffeBundle> {
+        let invalid = |reason: String| -> Error {
+            ErrorKind::InvalidSpiffeBundle(trust_domain.to_string(), reason).into()
+        };
+
+        let document: Value = serde_json::from_slice(json)?;
+        let document = document
+            .as_object()
+            .ok_or_else(|| invalid("not a JSON object".to_string()))?;
+
+        let mut bundle = SpiffeBundle::new(trust_domain.clone());
+
+        bundle.sequence = match document.get("spiffe_sequence") {
+            Some(sequence) => Some(
+                sequence
+                    .as_u64()
+                    .ok_or_else(|| invalid("spiffe_sequence is not an integer".to_string()))?,
+            ),
+            None => None,
+        };
+
+        bundle.refresh_hint = match document.get("spiffe_refresh_hint") {
+            Some(hint) => Some(Duration::from_secs(hint.as_u64().ok_or_else(|| {
+                invalid("spiffe_refresh_hint is not an integer".to_string())
+            })?)),
+            None => None,
+        };
+
+        let keys = document
+            .get("keys")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("keys is missing or not an array".to_string()))?;
+
+        for (index, key) in keys.iter().enumerate() {
+            let key = key
+                .as_object()
+                .ok_or_else(|| invalid(format!("key {} is not a JSON object", index)))?;
+            let invalid_key = |reason: String| invalid(format!("key {}: {}", index, reason));
+
+            match string_member(key, "use").map_err(invalid_key)? {
+                X509_SVID_USE => {
+                    let authority = x509_authority(key).map_err(invalid_key)?;
+                    bundle.x509.add_authority(authority);
+                }
+                JWT_SVID_USE => {
+                    let key_id = string_member(key, "kid").map_err(invalid_key)?;
+                    if key_id.is_empty() {
+                        return Err(invalid_key("kid is empty".to_string()));
+                    }
+                    let authority = public_key_from_jwk(key).map_err(invalid_key)?;
+                    if bundle.jwt.add_authority(key_id, authority).is_some() {
+                        return Err(invalid_key(format!("kid {} is not unique", key_id)));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    /// Serializes the bundle document. JWT authorities are written in key ID order.
+    pub fn to_json(&self) -> Result<String> {
+        let invalid = |reason: String| -> Error {
+            ErrorKind::InvalidSpiffeBundle(self.trust_domain().to_string(), reason).into()
+        };
+
+        let mut keys = Vec::new();
+
+        for authority in self.x509.authorities() {
+            let mut key = Map::new();
+            key.insert("use".to_string(), X509_SVID_USE.into());
+            let public_key = authority.public_key()?;
+            public_key_to_jwk(&public_key, &mut key).map_err(invalid)?;
+            let der = authority.to_der()?;
+            key.insert(
+                "x5c".to_string(),
+                Value::Array(vec![base64::encode_block(&der).into()]),
+            );
+            keys.push(Value::Object(key));
+        }
+
+        let mut key_ids: Vec<&String> = self.jwt.authorities().keys().collect();
+        key_ids.sort();
+        for key_id in key_ids {
+            let mut key = Map::new();
+            key.insert("use".to_string(), JWT_SVID_USE.into());
+            key.insert("kid".to_string(), key_id.as_str().into());
+            public_key_to_jwk(&self.jwt.authorities()[key_id], &mut key).map_err(invalid)?;
+            keys.push(Value::Object(key));
+        }
+
+        let mut document = Map::new();
+        document.insert("keys".to_string(), Value::Array(keys));
+        if let Some(sequence) = self.sequence {
+            document.insert("spiffe_sequence".to_string(), sequence.into());
+        }
+        if let Some(hint) = self.refresh_hint {
+            document.insert("spiffe_refresh_hint".to_string(), hint.as_secs().into());
+        }
+
+        Ok(serde_json::to_string(&document)?)
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        self.x509.trust_domain()
+    }
+
+    pub fn x509_bundle(&self) -> &X509Bundle {
+        &self.x509
+    }
+
+    pub fn x509_bundle_mut(&mut self) -> &mut X509Bundle {
+        &mut self.x509
+    }
+
+    pub fn jwt_bundle(&self) -> &JwtBundle {
+        &self.jwt
+    }
+
+    pub fn jwt_bundle_mut(&mut self) -> &mut JwtBundle {
+        &mut self.jwt
+    }
+
+    /// Splits the bundle into its X.509 and JWT bundles.
+    pub fn into_bundles(self) -> (X509Bundle, JwtBundle) {
+        (self.x509, self.jwt)
+    }
+
+    /// The `spiffe_sequence` number, which increases with every change to the bundle.
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    pub fn set_sequence(&mut self, sequence: Option<u64>) {
+        self.sequence = sequence;
+    }
+
+    /// The `spiffe_refresh_hint`, how often consumers should check for updates.
+    pub fn refresh_hint(&self) -> Option<Duration> {
+        self.refresh_hint
+    }
+
+    pub fn set_refresh_hint(&mut self, refresh_hint: Option<Duration>) {
+        self.refresh_hint = refresh_hint;
+    }
+}
+
+fn x509_authority(key: &Map<String, Value>) -> jwk::JwkResult<openssl::x509::X509> {
+    let chain = match key.get("x5c").and_then(Value::as_array) {
+        Some(chain) if chain.len() == 1 => chain,
+        _ => return Err("x5c must hold exactly one certificate".to_string()),
+    };
+    let encoded = chain[0]
+        .as_str()
+        .ok_or_else(|| "x5c certificate is not a string".to_string())?;
+
+    // x5c uses standard base64, unlike the other JWK members
+    let der = base64::decode_block(encoded).map_err(|_| "x5c is not valid base64".to_string())?;
+    openssl::x509::X509::from_der(&der).map_err(|e| format!("x5c is not a certificate: {}", e))
+}
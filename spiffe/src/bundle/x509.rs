@@ -0,0 +1,185 @@
+use crate::bundle::{ErrorKind, Result, ResultExt};
+use crate::svid::x509::{certificates_from_der, certificates_from_pem};
+use crate::uri::{TrustDomain, URI};
+use std::collections::hash_map::{HashMap, Values};
+use std::fmt;
+
+type OpenSSlX509Cert = ::openssl::x509::X509;
+type OpenSSlX509CertRef = ::openssl::x509::X509Ref;
+
+/// The X.509 authorities of a single trust domain, used as trust anchors when verifying
+/// X.509-SVIDs issued in that trust domain.
+#[derive(Clone)]
+pub struct X509Bundle {
+    trust_domain: TrustDomain,
+    authorities: Vec<OpenSSlX509Cert>,
+}
+
+impl X509Bundle {
+    pub fn new(trust_domain: TrustDomain) -> X509Bundle {
+        X509Bundle {
+            trust_domain,
+            authorities: Vec::new(),
+        }
+    }
+
+    pub fn from_authorities(
+        trust_domain: TrustDomain,
+        authorities: Vec<OpenSSlX509Cert>,
+    ) -> X509Bundle {
+        X509Bundle {
+            trust_domain,
+            authorities,
+        }
+    }
+
+    /// Parses ASN.1 DER encoded certificates concatenated back to back, which is how the
+    /// Workload API delivers bundles.
+    pub fn from_der(trust_domain: TrustDomain, der: &[u8]) -> Result<X509Bundle> {
+        let authorities = certificates_from_der(der)
+            .chain_err(|| ErrorKind::InvalidBundle(trust_domain.to_string()))?;
+
+        Ok(X509Bundle {
+            trust_domain,
+            authorities,
+        })
+    }
+
+    /// Parses one or more PEM certificates, as found in bundle files on disk.
+    pub fn from_pem(trust_domain: TrustDomain, pem: &[u8]) -> Result<X509Bundle> {
+        let authorities = certificates_from_pem(pem)
+            .chain_err(|| ErrorKind::InvalidBundle(trust_domain.to_string()))?;
+
+        Ok(X509Bundle {
+            trust_domain,
+            authorities,
+        })
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn authorities(&self) -> &[OpenSSlX509Cert] {
+        &self.authorities
+    }
+
+    /// Adds an authority, unless the bundle already contains it. Returns whether it was added.
+    pub fn add_authority(&mut self, authority: OpenSSlX509Cert) -> bool {
+        if self.contains_authority(&authority) {
+            return false;
+        }
+        self.authorities.push(authority);
+        true
+    }
+
+    /// Removes an authority. Returns whether the bundle contained it.
+    pub fn remove_authority(&mut self, authority: &OpenSSlX509CertRef) -> bool {
+        let len = self.authorities.len();
+        self.authorities.retain(|a| !same_certificate(a, authority));
+        self.authorities.len() != len
+    }
+
+    pub fn contains_authority(&self, authority: &OpenSSlX509CertRef) -> bool {
+        self.authorities
+            .iter()
+            .any(|a| same_certificate(a, authority))
+    }
+
+    /// Adds the authorities of `other` that the bundle does not already contain.
+    pub(crate) fn merge(&mut self, other: X509Bundle) {
+        for authority in other.authorities {
+            self.add_authority(authority);
+        }
+    }
+}
+
+/// X.509 bundles keyed by trust domain, e.g. a workload's own bundle and the bundles of the trust
+/// domains it federates with.
+#[derive(Clone, Debug, Default)]
+pub struct X509BundleSet {
+    bundles: HashMap<TrustDomain, X509Bundle>,
+}
+
+impl X509BundleSet {
+    pub fn new() -> X509BundleSet {
+        X509BundleSet::default()
+    }
+
+    /// Inserts a bundle, returning the bundle it replaces for the same trust domain, if any.
+    pub fn insert(&mut self, bundle: X509Bundle) -> Option<X509Bundle> {
+        self.bundles.insert(bundle.trust_domain().clone(), bundle)
+    }
+
+    pub fn remove(&mut self, trust_domain: &TrustDomain) -> Option<X509Bundle> {
+        self.bundles.remove(trust_domain)
+    }
+
+    pub fn get(&self, trust_domain: &TrustDomain) -> Option<&X509Bundle> {
+        self.bundles.get(trust_domain)
+    }
+
+    /// The bundle to verify SVIDs for `id` against, i.e. the bundle of its trust domain.
+    pub fn get_for_id(&self, id: &URI) -> Option<&X509Bundle> {
+        self.get(id.trust_domain())
+    }
+
+    pub fn contains(&self, trust_domain: &TrustDomain) -> bool {
+        self.bundles.contains_key(trust_domain)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    /// The bundles in no particular order.
+    pub fn iter(&self) -> Values<'_, TrustDomain, X509Bundle> {
+        self.bundles.values()
+    }
+
+    /// The trust domains of the bundles, sorted by name.
+    pub fn trust_domains(&self) -> Vec<TrustDomain> {
+        let mut trust_domains: Vec<TrustDomain> = self.bundles.keys().cloned().collect();
+        trust_domains.sort();
+        trust_domains
+    }
+
+    /// Inserts a bundle, merging it into the bundle already held for the same trust domain.
+    pub(crate) fn merge(&mut self, bundle: X509Bundle) {
+        match self.bundles.get_mut(bundle.trust_domain()) {
+            Some(existing) => existing.merge(bundle),
+            None => {
+                self.insert(bundle);
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a X509BundleSet {
+    type Item = &'a X509Bundle;
+    type IntoIter = Values<'a, TrustDomain, X509Bundle>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn same_certificate(a: &OpenSSlX509CertRef, b: &OpenSSlX509CertRef) -> bool {
+    match (a.to_der(), b.to_der()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl fmt::Debug for X509Bundle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("X509Bundle")
+            .field("trust_domain", &self.trust_domain)
+            .field("authorities", &self.authorities.len())
+            .finish()
+    }
+}
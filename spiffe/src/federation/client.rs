@@ -0,0 +1,342 @@
+use crate::bundle::spiffe::SpiffeBundle;
+use crate::bundle::x509::X509Bundle;
+use crate::federation::{Error, ErrorKind, Result, ResultExt};
+use crate::federation::{DEFAULT_REFRESH_HINT, DEFAULT_TIMEOUT};
+use crate::svid::x509::X509;
+use crate::svid::SVID;
+use crate::uri::{TrustDomain, URI};
+use hyper::client::conn;
+use hyper::header::HOST;
+use hyper::{Body, Request, Uri};
+use log::warn;
+use openssl::ssl::{ConnectConfiguration, SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::X509 as OpenSSlX509Cert;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::runtime::{Builder, Runtime};
+
+/// Fetches are retried at least this often after a failure.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Refresh hints below this are rounded up, so a misconfigured endpoint is not polled in a loop.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How the client authenticates a bundle endpoint.
+#[derive(Clone, Debug)]
+pub enum BundleEndpointProfile {
+    /// `https_web`: the endpoint presents a certificate for its host name issued by a WebPKI
+    /// authority.
+    HttpsWeb,
+    /// `https_spiffe`: the endpoint presents an X.509-SVID for `endpoint_id`, verified against a
+    /// bundle obtained beforehand for the endpoint's trust domain.
+    HttpsSpiffe {
+        endpoint_id: URI,
+        bundle: X509Bundle,
+    },
+}
+
+/// A client for a foreign trust domain's SPIFFE bundle endpoint.
+#[derive(Debug)]
+pub struct BundleEndpointClient {
+    url: Uri,
+    trust_domain: TrustDomain,
+    profile: BundleEndpointProfile,
+    web_roots: Option<Vec<OpenSSlX509Cert>>,
+    timeout: Duration,
+}
+
+impl BundleEndpointClient {
+    pub fn new(
+        url: &str,
+        trust_domain: TrustDomain,
+        profile: BundleEndpointProfile,
+    ) -> Result<BundleEndpointClient> {
+        let invalid = || Error::from(ErrorKind::InvalidEndpointURL(url.to_string()));
+
+        let url: Uri = url.parse().map_err(|_| invalid())?;
+        if url.scheme_str() != Some("https") || url.host().is_none() {
+            return Err(invalid());
+        }
+
+        Ok(BundleEndpointClient {
+            url,
+            trust_domain,
+            profile,
+            web_roots: None,
+            timeout: *DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Trusts `roots` instead of the system's WebPKI authorities under the `https_web` profile.
+    pub fn set_web_roots(&mut self, roots: Vec<OpenSSlX509Cert>) {
+        self.web_roots = Some(roots);
+    }
+
+    /// Bounds each fetch, from connecting to reading the response. Defaults to 30 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn profile(&self) -> &BundleEndpointProfile {
+        &self.profile
+    }
+
+    /// Fetches the current bundle of the foreign trust domain.
+    ///
+    /// Blocks on a runtime of its own, so it panics when called from within a Tokio runtime. Use
+    /// `fetch_async` there instead.
+    pub fn fetch(&self) -> Result<SpiffeBundle> {
+        let mut runtime = runtime()?;
+        self.fetch_on(&mut runtime)
+    }
+
+    /// Fetches the current bundle of the foreign trust domain on the current Tokio runtime.
+    pub async fn fetch_async(&self) -> Result<SpiffeBundle> {
+        let body = match tokio::time::timeout(self.timeout, self.get()).await {
+            Ok(body) => body?,
+            Err(_) => return Err(ErrorKind::Timeout.into()),
+        };
+
+        Ok(SpiffeBundle::parse(self.trust_domain.clone(), &body)?)
+    }
+
+    /// Fetches the bundle on a background thread, again after every `spiffe_refresh_hint` or,
+    /// for bundles without one, `default_refresh_hint` (5 minutes if `None`). Changed bundles are
+    /// published to subscribers.
+    ///
+    /// Under the `https_spiffe` profile, when the endpoint belongs to the foreign trust domain
+    /// itself, each fetched bundle becomes the one the endpoint is authenticated against, so the
+    /// endpoint can rotate its authorities.
+    pub fn watch(self, default_refresh_hint: Option<Duration>) -> Result<BundleWatcher> {
+        let default_refresh_hint = default_refresh_hint.unwrap_or(*DEFAULT_REFRESH_HINT);
+        let runtime = runtime()?;
+        let shared = Arc::new(Mutex::new(Subscribers::default()));
+        let (stop, stopped) = mpsc::channel();
+
+        let thread_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || {
+            self.run(runtime, default_refresh_hint, &thread_shared, &stopped)
+        });
+
+        Ok(BundleWatcher {
+            shared,
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+
+    fn run(
+        mut self,
+        mut runtime: Runtime,
+        default_refresh_hint: Duration,
+        shared: &Mutex<Subscribers>,
+        stopped: &Receiver<()>,
+    ) {
+        let mut refresh_interval = default_refresh_hint;
+
+        loop {
+            let delay = match self.fetch_on(&mut runtime) {
+                Ok(bundle) => {
+                    refresh_interval = bundle
+                        .refresh_hint()
+                        .unwrap_or(default_refresh_hint)
+                        .max(MIN_REFRESH_INTERVAL);
+                    self.rotate_endpoint_bundle(&bundle);
+                    lock(shared).publish(bundle);
+                    refresh_interval
+                }
+                Err(e) => {
+                    warn!("Unable to fetch bundle of {}: {}.", self.trust_domain, e);
+                    refresh_interval.min(MAX_RETRY_INTERVAL)
+                }
+            };
+
+            match stopped.recv_timeout(delay) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+        }
+    }
+
+    fn rotate_endpoint_bundle(&mut self, fetched: &SpiffeBundle) {
+        if let BundleEndpointProfile::HttpsSpiffe {
+            endpoint_id,
+            bundle,
+        } = &mut self.profile
+        {
+            if endpoint_id.trust_domain() == fetched.trust_domain() {
+                *bundle = fetched.x509_bundle().clone();
+            }
+        }
+    }
+
+    fn fetch_on(&self, runtime: &mut Runtime) -> Result<SpiffeBundle> {
+        runtime.block_on(self.fetch_async())
+    }
+
+    async fn get(&self) -> Result<Vec<u8>> {
+        // Checked in `new`
+        let host = self.url.host().unwrap_or_default();
+        let port = self.url.port_u16().unwrap_or(443);
+        // IPv6 hosts keep their brackets in URLs, but neither connecting nor the SNI and host
+        // name checks expect them
+        let address = host.trim_start_matches('[').trim_end_matches(']');
+
+        let tcp = TcpStream::connect((address, port)).await?;
+        let tls = tokio_openssl::connect(self.ssl_config()?, address, tcp)
+            .await
+            .map_err(|e| ErrorKind::TLSHandshakeFailure(e.to_string()))?;
+
+        if let BundleEndpointProfile::HttpsSpiffe {
+            endpoint_id,
+            bundle,
+        } = &self.profile
+        {
+            authenticate_endpoint(tls.ssl().peer_cert_chain(), endpoint_id, bundle)?;
+        }
+
+        let (mut sender, connection) = conn::handshake(tls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Bundle endpoint connection failed: {}.", e);
+            }
+        });
+
+        let path = self.url.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let authority = self.url.authority().map(|a| a.as_str()).unwrap_or(host);
+        let request = Request::get(path)
+            .header(HOST, authority)
+            .body(Body::empty())?;
+
+        let response = sender.send_request(request).await?;
+        if !response.status().is_success() {
+            return Err(ErrorKind::UnexpectedStatus(response.status().as_u16()).into());
+        }
+
+        Ok(hyper::body::to_bytes(response.into_body()).await?.to_vec())
+    }
+
+    fn ssl_config(&self) -> Result<ConnectConfiguration> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+
+        match self.profile {
+            BundleEndpointProfile::HttpsWeb => {
+                if let Some(roots) = &self.web_roots {
+                    let mut store = X509StoreBuilder::new()?;
+                    for root in roots {
+                        store.add_cert(root.clone())?;
+                    }
+                    builder.set_cert_store(store.build());
+                }
+                Ok(builder.build().configure()?)
+            }
+            BundleEndpointProfile::HttpsSpiffe { .. } => {
+                // SVIDs are not issued for host names, so the chain is verified against the
+                // bundle after the handshake instead
+                builder.set_verify(SslVerifyMode::NONE);
+                let mut config = builder.build().configure()?;
+                config.set_verify_hostname(false);
+                Ok(config)
+            }
+        }
+    }
+}
+
+/// Checks that the endpoint presented a valid X.509-SVID for `endpoint_id`.
+fn authenticate_endpoint(
+    chain: Option<&openssl::stack::StackRef<OpenSSlX509Cert>>,
+    endpoint_id: &URI,
+    bundle: &X509Bundle,
+) -> Result<()> {
+    let chain: Vec<OpenSSlX509Cert> = chain
+        .map(|chain| chain.iter().map(|cert| cert.to_owned()).collect())
+        .unwrap_or_default();
+
+    let id = SVID::<X509>::from_x509_chain(chain, None, None)
+        .and_then(|svid| svid.verify(bundle))
+        .chain_err(|| ErrorKind::EndpointAuthenticationFailure(endpoint_id.to_string()))?;
+
+    if &id != endpoint_id {
+        return Err(
+            ErrorKind::UnexpectedEndpointId(endpoint_id.to_string(), id.to_string()).into(),
+        );
+    }
+
+    Ok(())
+}
+
+fn runtime() -> Result<Runtime> {
+    Ok(Builder::new().basic_scheduler().enable_all().build()?)
+}
+
+#[derive(Default)]
+struct Subscribers {
+    latest: Option<SpiffeBundle>,
+    latest_json: Option<String>,
+    senders: Vec<Sender<SpiffeBundle>>,
+}
+
+impl Subscribers {
+    fn publish(&mut self, bundle: SpiffeBundle) {
+        // Endpoints may serve the same bundle without bumping the sequence number, so changes
+        // are detected on the serialized form
+        let json = bundle.to_json().ok();
+        if json.is_some() && json == self.latest_json {
+            return;
+        }
+
+        self.senders
+            .retain(|sender| sender.send(bundle.clone()).is_ok());
+        self.latest = Some(bundle);
+        self.latest_json = json;
+    }
+}
+
+fn lock(shared: &Mutex<Subscribers>) -> std::sync::MutexGuard<'_, Subscribers> {
+    // Publishing cannot leave the subscribers inconsistent, so a poisoned lock is still usable
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Keeps a foreign trust domain's bundle up to date in the background. Dropping the watcher stops
+/// it.
+pub struct BundleWatcher {
+    shared: Arc<Mutex<Subscribers>>,
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BundleWatcher {
+    /// The most recently fetched bundle, if any fetch has succeeded yet.
+    pub fn latest(&self) -> Option<SpiffeBundle> {
+        lock(&self.shared).latest.clone()
+    }
+
+    /// Receives every changed bundle from now on, starting with the latest one if there is one.
+    pub fn subscribe(&self) -> Receiver<SpiffeBundle> {
+        let (sender, receiver) = mpsc::channel();
+        let mut shared = lock(&self.shared);
+        if let Some(latest) = &shared.latest {
+            // The receiver is still in scope, so this cannot fail
+            let _ = sender.send(latest.clone());
+        }
+        shared.senders.push(sender);
+        receiver
+    }
+}
+
+impl Drop for BundleWatcher {
+    fn drop(&mut self) {
+        // Disconnecting wakes the thread, which exits after any fetch in flight
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
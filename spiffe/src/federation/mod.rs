@@ -0,0 +1,73 @@
+pub mod client;
+pub mod server;
+
+use crate::bundle;
+use crate::svid;
+use crate::uri;
+use crate::workload;
+use error_chain::error_chain;
+use lazy_static::lazy_static;
+use std::time::Duration;
+
+lazy_static! {
+    static ref DEFAULT_TIMEOUT: Duration = Duration::new(30, 0);
+    static ref DEFAULT_REFRESH_HINT: Duration = Duration::new(300, 0);
+}
+
+error_chain! {
+    errors {
+        InvalidEndpointURL(url: String) {
+            description("An error during the configuration of a bundle endpoint")
+            display("Invalid bundle endpoint URL {}: Must be an https URL with a host", url)
+        }
+
+        TLSHandshakeFailure(reason: String) {
+            description("An error during the TLS handshake with a bundle endpoint")
+            display("TLS handshake with bundle endpoint failed: {}", reason)
+        }
+
+        EndpointAuthenticationFailure(expected: String) {
+            description("An error during the authentication of a bundle endpoint")
+            display("Unable to authenticate bundle endpoint as {}", expected)
+        }
+
+        UnexpectedEndpointId(expected: String, actual: String) {
+            description("An error during the authentication of a bundle endpoint")
+            display("Bundle endpoint presented SPIFFE ID {}, expected {}", actual, expected)
+        }
+
+        UnexpectedStatus(status: u16) {
+            description("An error during the fetch of a bundle")
+            display("Bundle endpoint responded with HTTP status {}", status)
+        }
+
+        Timeout {
+            description("An error during the fetch of a bundle")
+            display("Bundle endpoint did not respond in time")
+        }
+
+        MissingIdentityKey {
+            description("An error during the configuration of a bundle endpoint")
+            display("Bundle endpoint identity has no private key")
+        }
+
+        BundleNotFound(trust_domain: String) {
+            description("An error during the update of a served bundle")
+            display("No bundle found for trust domain {}", trust_domain)
+        }
+    }
+
+    links {
+        Bundle(bundle::Error, bundle::ErrorKind);
+        Uri(uri::Error, uri::ErrorKind);
+        X509SVID(svid::x509::Error, svid::x509::ErrorKind);
+        Workload(workload::Error, workload::ErrorKind);
+    }
+
+    foreign_links {
+        SSL(::openssl::error::ErrorStack);
+        Io(::std::io::Error);
+        Hyper(::hyper::Error);
+        HTTP(::hyper::http::Error);
+    }
+}
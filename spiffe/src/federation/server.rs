@@ -0,0 +1,391 @@
+use crate::bundle::jwt::JwtBundle;
+use crate::bundle::spiffe::SpiffeBundle;
+use crate::bundle::x509::X509Bundle;
+use crate::federation::DEFAULT_REFRESH_HINT;
+use crate::federation::{ErrorKind, Result};
+use crate::svid::x509::X509;
+use crate::svid::SVID;
+use crate::uri::TrustDomain;
+use crate::workload::source;
+use crate::workload::x509::{self, X509Client, X509Payload};
+use crate::{bundle, workload};
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use futures::{pin_mut, Stream, StreamExt};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use log::warn;
+use openssl::ssl::{SslAcceptor, SslMethod};
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::Builder;
+
+/// How the server authenticates itself to the partners fetching the bundle.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServerProfile {
+    /// `https_web`: a certificate for the endpoint's host name issued by a WebPKI authority.
+    HttpsWeb,
+    /// `https_spiffe`: an X.509-SVID, which partners verify against a bundle of its trust domain.
+    HttpsSpiffe,
+}
+
+/// Serves a trust domain's bundle in the SPIFFE bundle format, for foreign trust domains to
+/// federate with.
+///
+/// The served bundle carries a `spiffe_sequence` that increases with every change to its
+/// authorities and the server's `spiffe_refresh_hint`. Clones share their bundle and identity, so
+/// either can be updated while the server is running.
+#[derive(Clone)]
+pub struct BundleEndpointServer {
+    trust_domain: TrustDomain,
+    profile: ServerProfile,
+    refresh_hint: Duration,
+    shared: Arc<Mutex<State>>,
+}
+
+struct State {
+    acceptor: SslAcceptor,
+    bundle: Option<SpiffeBundle>,
+    json: Option<String>,
+}
+
+impl BundleEndpointServer {
+    /// Serves under the `https_web` profile with `identity`, a certificate chain for the
+    /// endpoint's host name and its private key.
+    pub fn https_web(
+        trust_domain: TrustDomain,
+        identity: &X509,
+        refresh_hint: Option<Duration>,
+    ) -> Result<BundleEndpointServer> {
+        BundleEndpointServer::new(
+            trust_domain,
+            ServerProfile::HttpsWeb,
+            identity,
+            refresh_hint,
+        )
+    }
+
+    /// Serves under the `https_spiffe` profile with `svid`, which must have its private key.
+    pub fn https_spiffe(
+        trust_domain: TrustDomain,
+        svid: &SVID<X509>,
+        refresh_hint: Option<Duration>,
+    ) -> Result<BundleEndpointServer> {
+        BundleEndpointServer::new(trust_domain, ServerProfile::HttpsSpiffe, svid, refresh_hint)
+    }
+
+    fn new(
+        trust_domain: TrustDomain,
+        profile: ServerProfile,
+        identity: &X509,
+        refresh_hint: Option<Duration>,
+    ) -> Result<BundleEndpointServer> {
+        let state = State {
+            acceptor: acceptor(identity)?,
+            bundle: None,
+            json: None,
+        };
+
+        Ok(BundleEndpointServer {
+            trust_domain,
+            profile,
+            refresh_hint: refresh_hint.unwrap_or(*DEFAULT_REFRESH_HINT),
+            shared: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn profile(&self) -> ServerProfile {
+        self.profile
+    }
+
+    /// The bundle being served, if one has been set.
+    pub fn bundle(&self) -> Option<SpiffeBundle> {
+        lock(&self.shared).bundle.clone()
+    }
+
+    /// Replaces the certificate chain and key presented to new connections, for instance when
+    /// the SVID rotates.
+    pub fn set_identity(&self, identity: &X509) -> Result<()> {
+        lock(&self.shared).acceptor = acceptor(identity)?;
+        Ok(())
+    }
+
+    /// Serves `bundle`. A sequence number it carries is kept if it is ahead of the server's.
+    pub fn set_bundle(&self, bundle: SpiffeBundle) -> Result<()> {
+        self.check_trust_domain(bundle.trust_domain())?;
+        let floor = bundle.sequence();
+        self.update(floor, |served| *served = bundle)
+    }
+
+    /// Serves `bundle` as the X.509 authorities, keeping the JWT authorities.
+    pub fn set_x509_bundle(&self, bundle: X509Bundle) -> Result<()> {
+        self.check_trust_domain(bundle.trust_domain())?;
+        self.update(None, |served| *served.x509_bundle_mut() = bundle)
+    }
+
+    /// Serves `bundle` as the JWT authorities, keeping the X.509 authorities.
+    pub fn set_jwt_bundle(&self, bundle: JwtBundle) -> Result<()> {
+        self.check_trust_domain(bundle.trust_domain())?;
+        self.update(None, |served| *served.jwt_bundle_mut() = bundle)
+    }
+
+    /// Serves the X.509 authorities of the trust domain from a Workload API payload. Under the
+    /// `https_spiffe` profile, the payload's default SVID also becomes the server's identity.
+    pub fn update_from_payload(&self, payload: &X509Payload) -> Result<()> {
+        let bundle = payload
+            .bundles()
+            .get(&self.trust_domain)
+            .ok_or_else(|| ErrorKind::BundleNotFound(self.trust_domain.to_string()))?;
+        self.set_x509_bundle(bundle.clone())?;
+
+        if self.profile == ServerProfile::HttpsSpiffe {
+            if let Some(svid) = payload.svids().first() {
+                self.set_identity(svid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follows the Workload API through `client` on a background thread until the returned
+    /// handle is dropped, updating the served bundle with every payload. The stream is reopened
+    /// with backoff whenever it ends or fails.
+    pub fn follow(&self, client: X509Client) -> Result<FollowHandle> {
+        self.follow_with(move || Ok(client.open_stream(None)?.map(x509::payload)))
+    }
+
+    /// Like `follow`, with the payloads read from the streams `open` returns.
+    pub fn follow_with<F, S>(&self, open: F) -> Result<FollowHandle>
+    where
+        F: FnMut() -> workload::Result<S> + Send + 'static,
+        S: Stream<Item = workload::Result<X509Payload>> + Unpin,
+    {
+        let runtime = Builder::new().basic_scheduler().enable_all().build()?;
+        let (stop, stopped) = oneshot::channel();
+
+        let server = self.clone();
+        let handle = thread::spawn(move || {
+            // A bad payload leaves the previous bundle in place until the next one
+            let update = |payload: X509Payload| {
+                if let Err(e) = server.update_from_payload(&payload) {
+                    warn!("Unable to update bundle of {}: {}.", server.trust_domain, e);
+                }
+            };
+            source::follow(runtime, open, update, stopped)
+        });
+
+        Ok(FollowHandle {
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+
+    /// Listens on `addr` and serves the bundle on a background thread until the returned handle
+    /// is dropped. Until a bundle is set, requests are answered with 503 Service Unavailable.
+    pub fn start(&self, addr: &SocketAddr) -> Result<BundleEndpointHandle> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let mut runtime = Builder::new().basic_scheduler().enable_all().build()?;
+        let (stop, stopped) = oneshot::channel();
+
+        let shared = Arc::clone(&self.shared);
+        let handle = thread::spawn(move || {
+            runtime.block_on(async move {
+                match TcpListener::from_std(listener) {
+                    Ok(listener) => accept(listener, shared, stopped).await,
+                    Err(e) => warn!("Unable to listen for bundle requests: {}.", e),
+                }
+            })
+        });
+
+        Ok(BundleEndpointHandle {
+            local_addr,
+            stop: Some(stop),
+            handle: Some(handle),
+        })
+    }
+
+    fn check_trust_domain(&self, trust_domain: &TrustDomain) -> Result<()> {
+        if trust_domain != &self.trust_domain {
+            return Err(bundle::Error::from(bundle::ErrorKind::TrustDomainMismatch(
+                self.trust_domain.to_string(),
+                trust_domain.to_string(),
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn update<F>(&self, floor: Option<u64>, change: F) -> Result<()>
+    where
+        F: FnOnce(&mut SpiffeBundle),
+    {
+        let mut state = lock(&self.shared);
+
+        let mut bundle = state
+            .bundle
+            .clone()
+            .unwrap_or_else(|| SpiffeBundle::new(self.trust_domain.clone()));
+        let sequence = state.bundle.as_ref().and_then(SpiffeBundle::sequence);
+        change(&mut bundle);
+
+        // Compare the documents with the old sequence number, so only real changes bump it
+        bundle.set_sequence(sequence);
+        bundle.set_refresh_hint(Some(self.refresh_hint));
+        let sequence = if state.bundle.is_none() || Some(bundle.to_json()?) != state.json {
+            Some(sequence.map_or(1, |sequence| sequence + 1))
+        } else {
+            sequence
+        };
+        // The floor applies even to unchanged contents, so the sequence never falls behind it
+        bundle.set_sequence(sequence.max(floor));
+
+        state.json = Some(bundle.to_json()?);
+        state.bundle = Some(bundle);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BundleEndpointServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BundleEndpointServer")
+            .field("trust_domain", &self.trust_domain)
+            .field("profile", &self.profile)
+            .field("refresh_hint", &self.refresh_hint)
+            .field("bundle", &lock(&self.shared).bundle)
+            .finish()
+    }
+}
+
+/// Keeps a bundle endpoint server running. Dropping the handle stops the server.
+pub struct BundleEndpointHandle {
+    local_addr: SocketAddr,
+    stop: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BundleEndpointHandle {
+    /// The address the server listens on, with the port assigned if port 0 was requested.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// Keeps a bundle endpoint server following the Workload API. Dropping the handle stops it.
+pub struct FollowHandle {
+    stop: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for FollowHandle {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for BundleEndpointHandle {
+    fn drop(&mut self) {
+        // Connections in flight are dropped with the runtime
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn acceptor(identity: &X509) -> Result<SslAcceptor> {
+    if identity.key().is_none() {
+        return Err(ErrorKind::MissingIdentityKey.into());
+    }
+
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+    identity.configure_ssl(&mut builder)?;
+    Ok(builder.build())
+}
+
+async fn accept(
+    mut listener: TcpListener,
+    shared: Arc<Mutex<State>>,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    loop {
+        let accepted = {
+            let next = listener.accept();
+            pin_mut!(next);
+            match future::select(next, &mut stopped).await {
+                Either::Left((accepted, _)) => accepted,
+                Either::Right(_) => return,
+            }
+        };
+
+        match accepted {
+            Ok((tcp, _)) => {
+                let acceptor = lock(&shared).acceptor.clone();
+                tokio::spawn(serve(acceptor, tcp, Arc::clone(&shared)));
+            }
+            Err(e) => warn!("Unable to accept bundle request: {}.", e),
+        }
+    }
+}
+
+async fn serve(acceptor: SslAcceptor, tcp: TcpStream, shared: Arc<Mutex<State>>) {
+    let tls = match tokio_openssl::accept(&acceptor, tcp).await {
+        Ok(tls) => tls,
+        Err(e) => {
+            warn!("TLS handshake with bundle endpoint client failed: {}.", e);
+            return;
+        }
+    };
+
+    let service = service_fn(move |request| {
+        let response = respond(&shared, &request);
+        async move { Ok::<_, Infallible>(response) }
+    });
+
+    if let Err(e) = Http::new().serve_connection(tls, service).await {
+        warn!("Bundle endpoint connection failed: {}.", e);
+    }
+}
+
+fn respond(shared: &Mutex<State>, request: &Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    match lock(shared).json.clone() {
+        Some(json) => {
+            let mut response = Response::new(Body::from(json));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            response
+        }
+        None => status(StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn lock(shared: &Mutex<State>) -> MutexGuard<'_, State> {
+    // Updates replace whole fields, so a poisoned lock is still usable
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -1,3 +1,7 @@
+pub mod bundle;
+pub mod federation;
 pub mod svid;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod uri;
 pub mod workload;
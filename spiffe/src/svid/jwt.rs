@@ -1,9 +1,27 @@
+use crate::bundle;
+use crate::bundle::jwk::base64url_decode;
+use crate::bundle::jwt::JwtBundle;
 use crate::svid::{SVIDKind, SVID};
+use crate::uri;
 use crate::uri::URI;
 use error_chain::error_chain;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKeyRef, Public};
+use openssl::rsa::Padding;
+use openssl::sign::{RsaPssSaltlen, Verifier};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::mem;
 use std::ops::Deref;
 use std::str::FromStr;
-use zeroize::Zeroize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::{Zeroize, Zeroizing};
+
+/// The last second of the year 9999, the latest NumericDate accepted in `exp` and `iat`.
+const MAX_NUMERIC_DATE: u64 = 253_402_300_799;
 
 error_chain! {
     errors {
@@ -11,6 +29,149 @@ error_chain! {
             description("An error occured during the parsing of the SPIFFE ID")
             display("The SPIFFE ID can not be parsed into a valid SPIFFE URI")
         }
+
+        UnknownKeyId(key_id: String) {
+            description("An error occured during the lookup of a JWT signing key")
+            display("No JWT signing key found for key ID {}", key_id)
+        }
+
+        MalformedToken(reason: String) {
+            description("An error occured during the parsing of a JWT-SVID")
+            display("Unable to parse JWT-SVID: {}", reason)
+        }
+
+        InvalidSubject(sub: String) {
+            description("An error occured during the parsing of a JWT-SVID subject")
+            display("Unable to parse JWT-SVID: Subject {} is not a valid SPIFFE ID", sub)
+        }
+
+        UnsupportedType(typ: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Header typ {} is neither JWT nor JOSE", typ)
+        }
+
+        MissingKeyId {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Header has no kid")
+        }
+
+        UnsupportedAlgorithm(alg: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Algorithm {} is not supported", alg)
+        }
+
+        KeyAlgorithmMismatch(key_id: String, alg: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Key {} cannot be used with algorithm {}", key_id, alg)
+        }
+
+        InvalidSignature {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Signature is invalid")
+        }
+
+        TokenExpired {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Token has expired")
+        }
+
+        AudienceMismatch(audience: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: Audience does not include {}", audience)
+        }
+
+        TrustDomainMismatch(svid: String, bundle: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: SVID trust domain {} does not match bundle trust domain {}", svid, bundle)
+        }
+
+        BundleNotFound(trust_domain: String) {
+            description("An error occured during the validation of a JWT-SVID")
+            display("Unable to validate JWT-SVID: No bundle found for trust domain {}", trust_domain)
+        }
+    }
+
+    links {
+        Bundle(bundle::Error, bundle::ErrorKind);
+        Uri(uri::Error, uri::ErrorKind);
+    }
+
+    foreign_links {
+        SSL(::openssl::error::ErrorStack);
+        Json(::serde_json::Error);
+    }
+}
+
+/// The JOSE header of a JWT-SVID.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+    typ: Option<String>,
+}
+
+impl JwtHeader {
+    /// The signature algorithm, such as `ES256` or `RS256`.
+    pub fn alg(&self) -> &str {
+        &self.alg
+    }
+
+    /// The ID of the key in the trust domain's JWT bundle that signed the token.
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    pub fn typ(&self) -> Option<&str> {
+        self.typ.as_deref()
+    }
+}
+
+/// The claims of a JWT-SVID.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JwtClaims {
+    sub: URI,
+    aud: Vec<String>,
+    exp: u64,
+    iat: Option<u64>,
+    other: Map<String, Value>,
+}
+
+impl JwtClaims {
+    /// The `sub` claim, the SPIFFE ID of the workload the token was issued to.
+    pub fn subject(&self) -> &URI {
+        &self.sub
+    }
+
+    /// The `aud` claim, which may be a single string or a list in the token.
+    pub fn audience(&self) -> &[String] {
+        &self.aud
+    }
+
+    /// The `exp` claim.
+    pub fn expiry(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.exp)
+    }
+
+    /// The `iat` claim, if present.
+    pub fn issued_at(&self) -> Option<SystemTime> {
+        self.iat.map(|iat| UNIX_EPOCH + Duration::from_secs(iat))
+    }
+
+    /// The claims other than `sub`, `aud`, `exp` and `iat`.
+    pub fn other(&self) -> &Map<String, Value> {
+        &self.other
+    }
+
+    /// All of the claims as JSON, with `aud` as a list.
+    pub fn to_map(&self) -> Map<String, Value> {
+        let mut claims = self.other.clone();
+        claims.insert("sub".to_string(), self.sub.as_str().into());
+        claims.insert("aud".to_string(), self.aud.clone().into());
+        claims.insert("exp".to_string(), self.exp.into());
+        if let Some(iat) = self.iat {
+            claims.insert("iat".to_string(), iat.into());
+        }
+        claims
     }
 }
 
@@ -20,24 +181,114 @@ impl SVIDKind for Jwt {}
 #[zeroize(drop)]
 pub struct Jwt {
     svid: String,
+    #[zeroize(skip)]
+    header: JwtHeader,
+    #[zeroize(skip)]
+    claims: JwtClaims,
 }
 
 impl Jwt {
-    pub fn new(svid: String) -> Jwt {
-        Jwt { svid }
+    /// Decodes a token in the JWS compact serialization. The signature is not checked.
+    pub fn new(svid: String) -> Result<Jwt> {
+        // Zeroized on drop if the token does not decode
+        let mut svid = Zeroizing::new(svid);
+        let (header, claims) = decode(&svid)?;
+        Ok(Jwt {
+            svid: mem::take(&mut *svid),
+            header,
+            claims,
+        })
     }
 
     pub fn svid(&self) -> &str {
         &self.svid
     }
+
+    pub fn header(&self) -> &JwtHeader {
+        &self.header
+    }
+
+    pub fn claims(&self) -> &JwtClaims {
+        &self.claims
+    }
+
+    /// When the token expires, from its `exp` claim.
+    pub fn expiry(&self) -> SystemTime {
+        self.claims.expiry()
+    }
+
+    /// Validates the token without the Workload API: the signature must verify with the key of
+    /// its `kid` in the bundle of its subject's trust domain, it must not have expired and
+    /// `audience` must be one of its audiences. ES256/384/512, RS256/384/512 and PS256/384/512
+    /// signatures are supported.
+    ///
+    /// Returns the SPIFFE ID the token authenticates.
+    pub fn verify(&self, bundle: &JwtBundle, audience: &str) -> Result<URI> {
+        let subject = self.claims.subject();
+        if subject.trust_domain() != bundle.trust_domain() {
+            return Err(ErrorKind::TrustDomainMismatch(
+                subject.trust_domain().to_string(),
+                bundle.trust_domain().to_string(),
+            )
+            .into());
+        }
+
+        if let Some(typ) = self.header.typ() {
+            if typ != "JWT" && typ != "JOSE" {
+                return Err(ErrorKind::UnsupportedType(typ.to_string()).into());
+            }
+        }
+
+        let key_id = self.header.kid().ok_or(ErrorKind::MissingKeyId)?;
+        let key = bundle
+            .find_authority(key_id)
+            .ok_or_else(|| ErrorKind::UnknownKeyId(key_id.to_string()))?;
+
+        // Checked in `new`
+        let split = self.svid.rfind('.').unwrap_or_default();
+        let signature = base64url_decode(&self.svid[split + 1..]).unwrap_or_default();
+        if !verify_signature(
+            key_id,
+            self.header.alg(),
+            key,
+            &self.svid.as_bytes()[..split],
+            &signature,
+        )? {
+            return Err(ErrorKind::InvalidSignature.into());
+        }
+
+        if SystemTime::now() >= self.expiry() {
+            return Err(ErrorKind::TokenExpired.into());
+        }
+
+        if !self.claims.audience().iter().any(|aud| aud == audience) {
+            return Err(ErrorKind::AudienceMismatch(audience.to_string()).into());
+        }
+
+        Ok(subject.clone())
+    }
+}
+
+impl fmt::Debug for Jwt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The token is a bearer credential, so it is kept out of logs
+        f.debug_struct("Jwt")
+            .field("header", &self.header)
+            .field("claims", &self.claims)
+            .finish()
+    }
 }
 
 impl SVID<Jwt> {
-    pub fn new(svid: String, uri: &str) -> Result<SVID<Jwt>> {
-        Ok(SVID::<Jwt> {
-            doc: Jwt { svid },
-            uri: URI::from_str(uri).chain_err(|| ErrorKind::InvalidURI)?,
-        })
+    /// Decodes a JWT-SVID, taking its SPIFFE ID from the `sub` claim.
+    pub fn new(svid: String) -> Result<SVID<Jwt>> {
+        let doc = Jwt::new(svid)?;
+        let uri = doc.claims.sub.clone();
+        Ok(SVID::<Jwt> { doc, uri })
+    }
+
+    pub fn uri(&self) -> &URI {
+        &self.uri
     }
 }
 
@@ -49,3 +300,152 @@ impl Deref for SVID<Jwt> {
         &doc
     }
 }
+
+fn verify_signature(
+    key_id: &str,
+    alg: &str,
+    key: &PKeyRef<Public>,
+    input: &[u8],
+    signature: &[u8],
+) -> Result<bool> {
+    let (family, digest) = match alg {
+        "ES256" | "RS256" | "PS256" => (&alg[..2], MessageDigest::sha256()),
+        "ES384" | "RS384" | "PS384" => (&alg[..2], MessageDigest::sha384()),
+        "ES512" | "RS512" | "PS512" => (&alg[..2], MessageDigest::sha512()),
+        _ => return Err(ErrorKind::UnsupportedAlgorithm(alg.to_string()).into()),
+    };
+    let mismatch = || {
+        Error::from(ErrorKind::KeyAlgorithmMismatch(
+            key_id.to_string(),
+            alg.to_string(),
+        ))
+    };
+
+    let mut der = None;
+    match (family, key.id()) {
+        ("ES", Id::EC) => {
+            // The curve is fixed by the algorithm, ES512 uses P-521 (RFC 7518, 3.4)
+            let (nid, len) = match alg {
+                "ES256" => (Nid::X9_62_PRIME256V1, 32),
+                "ES384" => (Nid::SECP384R1, 48),
+                _ => (Nid::SECP521R1, 66),
+            };
+            if key.ec_key()?.group().curve_name() != Some(nid) {
+                return Err(mismatch());
+            }
+            if signature.len() != 2 * len {
+                return Ok(false);
+            }
+            let r = BigNum::from_slice(&signature[..len])?;
+            let s = BigNum::from_slice(&signature[len..])?;
+            der = Some(EcdsaSig::from_private_components(r, s)?.to_der()?);
+        }
+        ("RS", Id::RSA) | ("PS", Id::RSA) => {}
+        _ => return Err(mismatch()),
+    }
+
+    let mut verifier = Verifier::new(digest, key)?;
+    if family == "PS" {
+        verifier.set_rsa_padding(Padding::PKCS1_PSS)?;
+        verifier.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        verifier.set_rsa_mgf1_md(digest)?;
+    }
+    verifier.update(input)?;
+
+    // OpenSSL reports malformed signatures as errors rather than as a failed verification
+    Ok(verifier
+        .verify(der.as_deref().unwrap_or(signature))
+        .unwrap_or(false))
+}
+
+fn decode(token: &str) -> Result<(JwtHeader, JwtClaims)> {
+    let malformed =
+        |reason: &str| -> Error { ErrorKind::MalformedToken(reason.to_string()).into() };
+
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(malformed("Not in the JWS compact serialization"));
+    }
+    if base64url_decode(parts[2]).is_none() {
+        return Err(malformed("Signature is not valid base64url"));
+    }
+
+    let header = object(parts[0], "Header")?;
+    let header = JwtHeader {
+        alg: string(&header, "alg")?
+            .ok_or_else(|| malformed("Header has no alg"))?
+            .to_string(),
+        kid: string(&header, "kid")?.map(str::to_string),
+        typ: string(&header, "typ")?.map(str::to_string),
+    };
+
+    let mut other = object(parts[1], "Claims")?;
+    let sub = string(&other, "sub")?.ok_or_else(|| malformed("Claims have no sub"))?;
+    let sub = URI::from_str(sub).chain_err(|| ErrorKind::InvalidSubject(sub.to_string()))?;
+
+    let aud = match other.get("aud") {
+        Some(Value::String(aud)) => vec![aud.clone()],
+        Some(Value::Array(auds)) if !auds.is_empty() => auds
+            .iter()
+            .map(|aud| aud.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| malformed("Claim aud is not a list of strings"))?,
+        Some(_) => return Err(malformed("Claim aud is empty or not a string")),
+        None => return Err(malformed("Claims have no aud")),
+    };
+    let exp = numeric_date(&other, "exp")?.ok_or_else(|| malformed("Claims have no exp"))?;
+    let iat = numeric_date(&other, "iat")?;
+
+    for name in &["sub", "aud", "exp", "iat"] {
+        other.remove(*name);
+    }
+
+    Ok((
+        header,
+        JwtClaims {
+            sub,
+            aud,
+            exp,
+            iat,
+            other,
+        },
+    ))
+}
+
+fn object(part: &str, name: &str) -> Result<Map<String, Value>> {
+    let json = base64url_decode(part)
+        .ok_or_else(|| ErrorKind::MalformedToken(format!("{} is not valid base64url", name)))?;
+    match serde_json::from_slice(&json)? {
+        Value::Object(object) => Ok(object),
+        _ => Err(ErrorKind::MalformedToken(format!("{} is not a JSON object", name)).into()),
+    }
+}
+
+fn string<'a>(object: &'a Map<String, Value>, name: &str) -> Result<Option<&'a str>> {
+    match object.get(name) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(ErrorKind::MalformedToken(format!("{} is not a string", name)).into()),
+        None => Ok(None),
+    }
+}
+
+/// Reads a NumericDate, truncating fractional seconds. Dates past `MAX_NUMERIC_DATE` are rejected,
+/// so they can always be added to the Unix epoch.
+fn numeric_date(object: &Map<String, Value>, name: &str) -> Result<Option<u64>> {
+    match object.get(name) {
+        Some(value) => match value.as_u64().or_else(|| {
+            value
+                .as_f64()
+                .filter(|seconds| *seconds >= 0.0 && *seconds <= MAX_NUMERIC_DATE as f64)
+                .map(|seconds| seconds as u64)
+        }) {
+            Some(seconds) if seconds <= MAX_NUMERIC_DATE => Ok(Some(seconds)),
+            _ => Err(ErrorKind::MalformedToken(format!(
+                "Claim {} is not a non-negative number up to {}",
+                name, MAX_NUMERIC_DATE
+            ))
+            .into()),
+        },
+        None => Ok(None),
+    }
+}
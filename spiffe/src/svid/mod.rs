@@ -1,4 +1,5 @@
 pub mod jwt;
+mod secure;
 pub mod x509;
 
 use crate::uri::URI;
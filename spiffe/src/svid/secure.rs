@@ -0,0 +1,146 @@
+//! Handling of private key bytes: zeroized on drop and, with the `mlock` feature on Linux, kept
+//! out of swap and core dumps while they live.
+//!
+//! These are only the input buffers, which live while a key is parsed. The parsed `PKey` is
+//! allocated by OpenSSL, and is only kept out of swap and core dumps once `init_secure_heap` has
+//! been called.
+
+use std::ops::Deref;
+use zeroize::Zeroizing;
+
+/// Private key bytes, owned without copying the buffer they arrived in.
+pub(crate) struct SecretBytes {
+    bytes: Zeroizing<Vec<u8>>,
+}
+
+impl SecretBytes {
+    pub(crate) fn new(bytes: Vec<u8>) -> SecretBytes {
+        let bytes = Zeroizing::new(bytes);
+        #[cfg(all(feature = "mlock", target_os = "linux"))]
+        lock::lock(&bytes);
+        SecretBytes { bytes }
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(all(feature = "mlock", target_os = "linux"))]
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        // Zeroize before the pages can be swapped out again
+        zeroize::Zeroize::zeroize(&mut *self.bytes);
+        lock::unlock(&self.bytes);
+    }
+}
+
+#[cfg(all(feature = "mlock", target_os = "linux"))]
+pub(crate) mod lock {
+    use lazy_static::lazy_static;
+    use log::warn;
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+    use std::sync::Mutex;
+
+    extern "C" {
+        // Not exposed by openssl-sys
+        fn CRYPTO_secure_malloc_init(size: libc::size_t, minsize: libc::c_int) -> libc::c_int;
+    }
+
+    lazy_static! {
+        // Keys live in ordinary heap allocations, so a page may hold several of them. Locks do not
+        // nest, so each page counts the live keys on it and is only unlocked once none is left.
+        static ref LOCKED_PAGES: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
+    }
+
+    /// Locks the pages spanned by `bytes` into memory and excludes them from core dumps. Both are
+    /// best effort: failures, e.g. from a low `RLIMIT_MEMLOCK`, are logged and otherwise ignored.
+    pub(crate) fn lock(bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in pages(bytes) {
+            let count = locked.entry(page).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+
+            // Safe: the page is mapped for `bytes`, and neither call touches its contents
+            unsafe {
+                if libc::mlock(page as *const libc::c_void, page_size()) != 0 {
+                    warn!(
+                        "Unable to lock private key in memory: {}.",
+                        std::io::Error::last_os_error()
+                    );
+                }
+                if libc::madvise(page as *mut libc::c_void, page_size(), libc::MADV_DONTDUMP) != 0 {
+                    warn!(
+                        "Unable to exclude private key from core dumps: {}.",
+                        std::io::Error::last_os_error()
+                    );
+                }
+            }
+        }
+    }
+
+    /// Releases the pages spanned by `bytes` that no other live key is on. They may hold other
+    /// data, so they go back to being dumpable as well as swappable.
+    pub(crate) fn unlock(bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut locked = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+        for page in pages(bytes) {
+            match locked.get_mut(&page) {
+                Some(count) if *count > 1 => *count -= 1,
+                Some(_) => {
+                    locked.remove(&page);
+                    unsafe {
+                        libc::munlock(page as *const libc::c_void, page_size());
+                        libc::madvise(page as *mut libc::c_void, page_size(), libc::MADV_DODUMP);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// Returns whether the secure heap could be created. See `svid::x509::init_secure_heap`.
+    pub(crate) fn init_secure_heap(size: usize, min_size: usize) -> bool {
+        let min_size = match libc::c_int::try_from(min_size) {
+            Ok(min_size) => min_size,
+            Err(_) => return false,
+        };
+
+        // Safe: OpenSSL validates the sizes and rejects repeated initialization
+        match unsafe { CRYPTO_secure_malloc_init(size, min_size) } {
+            1 => true,
+            2 => {
+                warn!("OpenSSL secure heap created, but could not be locked in memory.");
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    /// The start addresses of the pages spanned by `bytes`.
+    fn pages(bytes: &[u8]) -> impl Iterator<Item = usize> {
+        let page = page_size();
+        let start = bytes.as_ptr() as usize & !(page - 1);
+        let end = bytes.as_ptr() as usize + bytes.len();
+        (start..end).step_by(page)
+    }
+}
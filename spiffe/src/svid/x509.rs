@@ -1,13 +1,31 @@
+use crate::bundle::x509::X509Bundle;
+#[cfg(all(feature = "mlock", target_os = "linux"))]
+use crate::svid::secure;
+use crate::svid::secure::SecretBytes;
 use crate::svid::{SVIDKind, SVID};
 use crate::uri;
-use crate::uri::URI;
+use crate::uri::{SpiffeIdRef, TrustDomain, URI};
 use error_chain::error_chain;
+use foreign_types::ForeignTypeRef;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::ec::EcKey;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::ssl::SslContextBuilder;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::verify::X509VerifyFlags;
+use openssl::x509::{X509StoreContext, X509VerifyResult};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fs;
 use std::ops::Deref;
 use std::path::Path;
 
 type OpenSSlX509Cert = ::openssl::x509::X509;
+type OpenSSlX509CertRef = ::openssl::x509::X509Ref;
 
 error_chain! {
     errors {
@@ -26,6 +44,26 @@ error_chain! {
             display("Unable to parse SVID: Not a valid DER")
         }
 
+        EmptyChain {
+            description("An error during the parsing of an SVID certificate chain")
+            display("Unable to parse SVID: Certificate chain is empty")
+        }
+
+        InvalidKey {
+            description("An error during the parsing of an SVID private key")
+            display("Unable to parse SVID: Not a valid PKCS#8, SEC1 EC or PKCS#1 RSA private key")
+        }
+
+        KeyMismatch {
+            description("An error during the validation of an SVID private key")
+            display("Unable to parse SVID: Private key does not match the leaf certificate")
+        }
+
+        SecureHeapUnavailable {
+            description("An error during the initialization of the OpenSSL secure heap")
+            display("Unable to initialize the OpenSSL secure heap")
+        }
+
         InvalidSAN {
             description("An error during the validation of SVID SANs")
             display("Unable to parse SVID: SANs do not contain a valid SPIFFE URI")
@@ -35,6 +73,91 @@ error_chain! {
             description("An error during the validation of SVID certificate")
             display("Multiple valid SPIFFE URIs found in SVID: {} & {}", first, next)
         }
+
+        NonSpiffeURI(uri: String) {
+            description("An error during the validation of SVID SANs")
+            display("Unable to parse SVID: URI SAN {} is not a valid SPIFFE ID", uri)
+        }
+
+        LeafIsCA {
+            description("An error during the validation of the X509-SVID leaf profile")
+            display("Invalid leaf SVID: Basic constraints must set CA to false")
+        }
+
+        LeafMissingDigitalSignature {
+            description("An error during the validation of the X509-SVID leaf profile")
+            display("Invalid leaf SVID: Key usage must include digitalSignature")
+        }
+
+        LeafHasKeyCertSign {
+            description("An error during the validation of the X509-SVID leaf profile")
+            display("Invalid leaf SVID: Key usage must not include keyCertSign")
+        }
+
+        LeafHasCRLSign {
+            description("An error during the validation of the X509-SVID leaf profile")
+            display("Invalid leaf SVID: Key usage must not include cRLSign")
+        }
+
+        SigningCertNotCA {
+            description("An error during the validation of the X509-SVID signing certificate profile")
+            display("Invalid signing certificate: Basic constraints must set CA to true")
+        }
+
+        SigningCertMissingKeyCertSign {
+            description("An error during the validation of the X509-SVID signing certificate profile")
+            display("Invalid signing certificate: Key usage must include keyCertSign")
+        }
+
+        SigningCertMissingSpiffeId {
+            description("An error during the validation of the X509-SVID signing certificate profile")
+            display("Invalid signing certificate: SANs do not contain a trust domain SPIFFE ID")
+        }
+
+        SigningCertIdHasPath(id: String) {
+            description("An error during the validation of the X509-SVID signing certificate profile")
+            display("Invalid signing certificate: SPIFFE ID {} must not have a path", id)
+        }
+
+        SigningCertTrustDomainMismatch(id: String, trust_domain: String) {
+            description("An error during the validation of the X509-SVID signing certificate profile")
+            display("Invalid signing certificate: SPIFFE ID {} is not in trust domain {}", id, trust_domain)
+        }
+
+        TrustDomainMismatch(svid: String, bundle: String) {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: SVID trust domain {} does not match bundle trust domain {}", svid, bundle)
+        }
+
+        UnknownAuthority {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: No path to an authority in the bundle")
+        }
+
+        CertificateExpired(depth: u32) {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: Certificate at chain depth {} has expired", depth)
+        }
+
+        CertificateNotYetValid(depth: u32) {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: Certificate at chain depth {} is not yet valid", depth)
+        }
+
+        SignatureFailure(depth: u32) {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: Signature of certificate at chain depth {} is invalid", depth)
+        }
+
+        VerificationFailed(depth: u32, reason: String) {
+            description("An error during the verification of an SVID")
+            display("Unable to verify SVID: Certificate at chain depth {} failed verification: {}", depth, reason)
+        }
+
+        ValidityOutOfRange(time: i64) {
+            description("An error during the issuance of a test certificate")
+            display("Unable to issue certificate: Unix time {} does not fit in time_t on this platform", time)
+        }
     }
 
     links {
@@ -53,39 +176,99 @@ impl<'a> From<&'a Path> for ErrorKind {
     }
 }
 
+// Raw X509_V_ERR_* codes from OpenSSL's x509_vfy.h
+const X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT: i32 = 2;
+const X509_V_ERR_CERT_SIGNATURE_FAILURE: i32 = 7;
+const X509_V_ERR_CERT_NOT_YET_VALID: i32 = 9;
+const X509_V_ERR_CERT_HAS_EXPIRED: i32 = 10;
+const X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT: i32 = 18;
+const X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN: i32 = 19;
+const X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY: i32 = 20;
+const X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE: i32 = 21;
+
 pub type Bundle = Vec<u8>;
+/// Raw private key bytes. Buffers passed to the constructors are zeroized once parsed, without
+/// being copied first.
 pub type Key = Vec<u8>;
 
 pub struct X509 {
-    cert: OpenSSlX509Cert,
-    key: Option<Key>,
+    chain: Vec<OpenSSlX509Cert>,
+    key: Option<PKey<Private>>,
     bundle: Option<Bundle>,
 }
 
 impl X509 {
-    pub fn new(cert: OpenSSlX509Cert, key: Option<Key>, bundle: Option<Bundle>) -> X509 {
-        X509 {
-            cert,
-            key: match key {
-                Some(k) => Some(k.to_vec()),
-                None => None,
-            },
-            bundle: match bundle {
-                Some(b) => Some(b.to_vec()),
-                None => None,
-            },
-        }
+    pub fn new(cert: OpenSSlX509Cert, key: Option<Key>, bundle: Option<Bundle>) -> Result<X509> {
+        X509::from_chain(cert, Vec::new(), key, bundle)
     }
 
+    /// Builds the document from a leaf certificate and the intermediates that chain it up to an
+    /// authority, in order. The key, if any, is parsed with `private_key_from_bytes` and must
+    /// belong to the leaf. An empty key or bundle counts as none, as the Workload API cannot tell
+    /// them apart.
+    pub fn from_chain(
+        leaf: OpenSSlX509Cert,
+        intermediates: Vec<OpenSSlX509Cert>,
+        key: Option<Key>,
+        bundle: Option<Bundle>,
+    ) -> Result<X509> {
+        let key = match key.filter(|k| !k.is_empty()).map(SecretBytes::new) {
+            Some(k) => {
+                let key = private_key_from_bytes(&k)?;
+                if !leaf.public_key()?.public_eq(&key) {
+                    return Err(ErrorKind::KeyMismatch.into());
+                }
+                Some(key)
+            }
+            None => None,
+        };
+
+        let mut chain = Vec::with_capacity(intermediates.len() + 1);
+        chain.push(leaf);
+        chain.extend(intermediates);
+
+        let bundle = bundle.filter(|b| !b.is_empty());
+
+        Ok(X509 { chain, key, bundle })
+    }
+
+    /// The leaf certificate. Same as `leaf()`.
     pub fn cert(&self) -> &OpenSSlX509Cert {
-        &self.cert
+        self.leaf()
     }
 
-    pub fn key(&self) -> Option<&Vec<u8>> {
-        match self.key {
-            Some(ref k) => Some(&k),
-            None => None,
+    pub fn leaf(&self) -> &OpenSSlX509Cert {
+        &self.chain[0]
+    }
+
+    /// The intermediate certificates following the leaf, in chain order.
+    pub fn intermediates(&self) -> &[OpenSSlX509Cert] {
+        &self.chain[1..]
+    }
+
+    /// The full certificate chain, leaf first.
+    pub fn chain(&self) -> &[OpenSSlX509Cert] {
+        &self.chain
+    }
+
+    /// Presents the certificate chain in TLS handshakes made with `builder`: the leaf as the
+    /// certificate and the intermediates as its chain. The private key is set too, if present.
+    pub fn configure_ssl(&self, builder: &mut SslContextBuilder) -> Result<()> {
+        builder.set_certificate(self.leaf())?;
+        for intermediate in self.intermediates() {
+            builder.add_extra_chain_cert(intermediate.clone())?;
+        }
+        if let Some(key) = self.key() {
+            builder.set_private_key(key)?;
         }
+        Ok(())
+    }
+
+    /// The private key of the leaf certificate. OpenSSL clears private key components when the key
+    /// is freed. The `mlock` feature alone only protects the bytes the key was parsed from; the
+    /// key itself is only kept out of swap and core dumps if `init_secure_heap` was called first.
+    pub fn key(&self) -> Option<&PKey<Private>> {
+        self.key.as_ref()
     }
 
     pub fn bundle(&self) -> Option<&Vec<u8>> {
@@ -98,37 +281,67 @@ impl X509 {
 
 impl fmt::Debug for X509 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let X509 { cert, .. } = self;
-        write!(
-            f,
-            "OpenSSL X509 Certificate: {{ {:?} }}",
-            cert.to_pem().unwrap_or_else(|_| vec![])
-        )
+        let X509 { chain, .. } = self;
+        let pems: Vec<Vec<u8>> = chain
+            .iter()
+            .map(|cert| cert.to_pem().unwrap_or_else(|_| vec![]))
+            .collect();
+        write!(f, "OpenSSL X509 Certificate: {{ {:?} }}", pems)
     }
 }
 
 impl SVIDKind for X509 {}
 
+/// A serializable description of an X.509-SVID that carries no key material, e.g. for inventories.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct X509Summary {
+    spiffe_id: URI,
+    serial: String,
+    not_before: i64,
+    not_after: i64,
+    chain_fingerprints: Vec<String>,
+}
+
+impl X509Summary {
+    pub fn spiffe_id(&self) -> &URI {
+        &self.spiffe_id
+    }
+
+    /// The certificate serial number in uppercase hex.
+    pub fn serial(&self) -> &str {
+        &self.serial
+    }
+
+    /// Start of the validity period in seconds since the Unix epoch.
+    pub fn not_before(&self) -> i64 {
+        self.not_before
+    }
+
+    /// End of the validity period in seconds since the Unix epoch.
+    pub fn not_after(&self) -> i64 {
+        self.not_after
+    }
+
+    /// Lowercase hex SHA-256 fingerprints of the certificate chain, leaf first.
+    pub fn chain_fingerprints(&self) -> &[String] {
+        &self.chain_fingerprints
+    }
+}
+
 impl SVID<X509> {
+    /// Parses the SVID from one or more PEM certificates, leaf first.
     pub fn from_pem(pem: &[u8], key: Option<Key>, bundle: Option<Bundle>) -> Result<SVID<X509>> {
-        let cert = OpenSSlX509Cert::from_pem(pem).chain_err(|| ErrorKind::InvalidPEM)?;
-
-        match SVID::<X509>::parse_uri(&cert) {
-            Ok(uri) => Ok(SVID::<X509> {
-                doc: X509::new(cert, key, bundle),
-                uri,
-            }),
-            Err(e) => Err(e.chain_err(|| ErrorKind::InvalidSAN)),
-        }
+        let chain = certificates_from_pem(pem)?;
+        SVID::<X509>::from_x509_chain(chain, key, bundle)
     }
 
     pub fn from_path(path: &Path, key: Option<&Path>, bundle: Option<&Path>) -> Result<SVID<X509>> {
         let contents = fs::read(path).chain_err(|| path)?;
-        let cert =
-            OpenSSlX509Cert::from_pem(contents.as_slice()).chain_err(|| ErrorKind::InvalidPEM)?;
+        let chain = certificates_from_pem(contents.as_slice())?;
 
         let key_contents = match key {
-            Some(path) => Some(fs::read(path).chain_err(|| path)?.to_vec()),
+            Some(path) => Some(fs::read(path).chain_err(|| path)?),
             None => None,
         };
         let bundle_contents = match bundle {
@@ -136,25 +349,14 @@ impl SVID<X509> {
             None => None,
         };
 
-        match SVID::<X509>::parse_uri(&cert) {
-            Ok(uri) => Ok(SVID::<X509> {
-                doc: X509::new(cert, key_contents, bundle_contents),
-                uri,
-            }),
-            Err(e) => Err(e.chain_err(|| ErrorKind::InvalidPEM)),
-        }
+        SVID::<X509>::from_x509_chain(chain, key_contents, bundle_contents)
     }
 
+    /// Parses the SVID from ASN.1 DER certificates concatenated back to back, leaf first, as
+    /// delivered by the Workload API.
     pub fn from_der(der: &[u8], key: Option<Key>, bundle: Option<Bundle>) -> Result<SVID<X509>> {
-        let cert = OpenSSlX509Cert::from_der(der).chain_err(|| ErrorKind::InvalidDER)?;
-
-        match SVID::<X509>::parse_uri(&cert) {
-            Ok(uri) => Ok(SVID::<X509> {
-                doc: X509::new(cert, key, bundle),
-                uri,
-            }),
-            Err(e) => Err(e.chain_err(|| ErrorKind::InvalidSAN)),
-        }
+        let chain = certificates_from_der(der)?;
+        SVID::<X509>::from_x509_chain(chain, key, bundle)
     }
 
     pub fn from_x509(
@@ -162,13 +364,40 @@ impl SVID<X509> {
         key: Option<Key>,
         bundle: Option<Bundle>,
     ) -> Result<SVID<X509>> {
-        match SVID::<X509>::parse_uri(&cert) {
-            Ok(uri) => Ok(SVID::<X509> {
-                doc: X509::new(cert, key, bundle),
-                uri,
-            }),
-            Err(e) => Err(e.chain_err(|| ErrorKind::InvalidPEM)),
+        SVID::<X509>::from_x509_chain(vec![cert], key, bundle)
+    }
+
+    /// Builds the SVID from a certificate chain, leaf first. The leaf must satisfy the X509-SVID
+    /// leaf profile and every intermediate must be a CA allowed to sign certificates. Intermediates
+    /// carrying a SPIFFE ID must name the trust domain of the leaf, without a path.
+    pub fn from_x509_chain(
+        chain: Vec<OpenSSlX509Cert>,
+        key: Option<Key>,
+        bundle: Option<Bundle>,
+    ) -> Result<SVID<X509>> {
+        let mut chain = chain.into_iter();
+        let leaf = chain.next().ok_or(ErrorKind::EmptyChain)?;
+        let intermediates: Vec<OpenSSlX509Cert> = chain.collect();
+
+        let uri = SVID::<X509>::parse_uri(&leaf)?;
+        validate_leaf_profile(&leaf)?;
+        for intermediate in intermediates.iter() {
+            validate_signing_authority(intermediate)?;
+            if let Some(id) = signing_spiffe_id(intermediate) {
+                if &validate_signing_id(id.clone())? != uri.trust_domain() {
+                    return Err(ErrorKind::SigningCertTrustDomainMismatch(
+                        id,
+                        uri.trust_domain().to_string(),
+                    )
+                    .into());
+                }
+            }
         }
+
+        Ok(SVID::<X509> {
+            doc: X509::from_chain(leaf, intermediates, key, bundle)?,
+            uri,
+        })
     }
 
     pub fn uri(&self) -> &URI {
@@ -181,34 +410,81 @@ impl SVID<X509> {
         &doc
     }
 
+    pub fn summary(&self) -> Result<X509Summary> {
+        let cert = self.cert();
+
+        Ok(X509Summary {
+            spiffe_id: self.uri().clone(),
+            serial: cert.serial_number().to_bn()?.to_hex_str()?.to_string(),
+            not_before: asn1_time_to_unix(cert.not_before())?,
+            not_after: asn1_time_to_unix(cert.not_after())?,
+            chain_fingerprints: self
+                .chain()
+                .iter()
+                .map(fingerprint)
+                .collect::<Result<Vec<String>>>()?,
+        })
+    }
+
+    /// Verifies the SVID against the authorities of its trust domain's bundle, checking the
+    /// validity period and signature of every certificate on the path to a bundle authority. Any
+    /// authority in the bundle may anchor the path, whether or not it is self-signed.
+    ///
+    /// Returns the SPIFFE ID the SVID authenticates.
+    pub fn verify(&self, bundle: &X509Bundle) -> Result<URI> {
+        if self.uri().trust_domain() != bundle.trust_domain() {
+            return Err(ErrorKind::TrustDomainMismatch(
+                self.uri().trust_domain().to_string(),
+                bundle.trust_domain().to_string(),
+            )
+            .into());
+        }
+
+        let mut store = X509StoreBuilder::new()?;
+        store.set_flags(X509VerifyFlags::PARTIAL_CHAIN)?;
+        for authority in bundle.authorities() {
+            store.add_cert(authority.clone())?;
+        }
+        let store = store.build();
+
+        let mut intermediates = Stack::new()?;
+        for intermediate in self.intermediates() {
+            intermediates.push(intermediate.clone())?;
+        }
+        let mut context = X509StoreContext::new()?;
+        let (verified, result, depth) = context.init(&store, self.cert(), &intermediates, |c| {
+            Ok((c.verify_cert()?, c.error(), c.error_depth()))
+        })?;
+
+        if verified {
+            Ok(self.uri().clone())
+        } else {
+            Err(verification_error(result, depth).into())
+        }
+    }
+
     pub fn match_spiffe_uri(&self, uri: &str) -> Result<bool> {
         Ok(self.uri().to_string().eq_ignore_ascii_case(uri))
     }
 
-    fn parse_uri(cert: &OpenSSlX509Cert) -> Result<URI> {
+    fn parse_uri(cert: &OpenSSlX509CertRef) -> Result<URI> {
         let sans = match cert.subject_alt_names() {
             Some(val) => val,
             None => return Err(ErrorKind::InvalidSAN.into()),
         };
 
-        let mut validated_uri: Option<URI> = None;
-        // Only allows one valid SPIFFE uri in SAN field per SPIFFE specification - returns error if multiple found
-        for san_entry in sans {
-            if let Some(uri) = san_entry.uri() {
-                if let Ok(spiffe_uri) = uri.parse::<URI>() {
-                    if let Some(validated_uri) = validated_uri {
-                        return Err(ErrorKind::MultipleURIFound(
-                            validated_uri.to_string(),
-                            uri.to_string(),
-                        )
-                        .into());
-                    }
-                    validated_uri = Some(spiffe_uri);
-                }
+        // The X509-SVID profile allows exactly one URI SAN, and it must be the SPIFFE ID
+        let mut uris = sans.iter().filter_map(|san| san.uri());
+        let uri = match (uris.next(), uris.next()) {
+            (Some(uri), None) => uri,
+            (Some(first), Some(next)) => {
+                return Err(ErrorKind::MultipleURIFound(first.to_string(), next.to_string()).into())
             }
-        }
+            (None, _) => return Err(ErrorKind::InvalidSAN.into()),
+        };
 
-        validated_uri.ok_or_else(|| Error::from(ErrorKind::InvalidSAN))
+        uri.parse::<URI>()
+            .chain_err(|| ErrorKind::NonSpiffeURI(uri.to_string()))
     }
 }
 
@@ -220,3 +496,205 @@ impl Deref for SVID<X509> {
         &doc
     }
 }
+
+pub(crate) fn asn1_time_to_unix(time: &Asn1TimeRef) -> Result<i64> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    Ok(i64::from(diff.days) * 86_400 + i64::from(diff.secs))
+}
+
+pub(crate) fn fingerprint(cert: &OpenSSlX509Cert) -> Result<String> {
+    let digest = cert.digest(MessageDigest::sha256())?;
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Checks the X509-SVID leaf profile: not a CA, and usable for digital signatures but not for
+/// signing certificates or CRLs.
+fn validate_leaf_profile(cert: &OpenSSlX509CertRef) -> Result<()> {
+    let (is_ca, key_usage) = extension_flags(cert);
+
+    if is_ca {
+        return Err(ErrorKind::LeafIsCA.into());
+    }
+
+    let key_usage = key_usage.ok_or_else(|| Error::from(ErrorKind::LeafMissingDigitalSignature))?;
+    if key_usage & openssl_sys::X509v3_KU_DIGITAL_SIGNATURE == 0 {
+        return Err(ErrorKind::LeafMissingDigitalSignature.into());
+    }
+    if key_usage & openssl_sys::X509v3_KU_KEY_CERT_SIGN != 0 {
+        return Err(ErrorKind::LeafHasKeyCertSign.into());
+    }
+    if key_usage & openssl_sys::X509v3_KU_CRL_SIGN != 0 {
+        return Err(ErrorKind::LeafHasCRLSign.into());
+    }
+
+    Ok(())
+}
+
+/// Checks the X509-SVID signing certificate profile: a CA allowed to sign certificates, whose
+/// SPIFFE ID names only its trust domain. Returns that trust domain.
+pub fn validate_signing_certificate(cert: &OpenSSlX509CertRef) -> Result<TrustDomain> {
+    validate_signing_authority(cert)?;
+
+    let id = cert
+        .subject_alt_names()
+        .and_then(|sans| sans.iter().find_map(|san| san.uri().map(str::to_string)))
+        .ok_or_else(|| Error::from(ErrorKind::SigningCertMissingSpiffeId))?;
+
+    validate_signing_id(id)
+}
+
+/// The first URI SAN of `cert` with the spiffe scheme, if any.
+fn signing_spiffe_id(cert: &OpenSSlX509CertRef) -> Option<String> {
+    cert.subject_alt_names().and_then(|sans| {
+        sans.iter().find_map(|san| {
+            san.uri()
+                .filter(|uri| uri.to_ascii_lowercase().starts_with("spiffe:"))
+                .map(str::to_string)
+        })
+    })
+}
+
+/// Checks that the SPIFFE ID of a signing certificate names only its trust domain, and returns
+/// that trust domain.
+fn validate_signing_id(id: String) -> Result<TrustDomain> {
+    if SpiffeIdRef::new(&id).is_ok() {
+        return Err(ErrorKind::SigningCertIdHasPath(id).into());
+    }
+
+    TrustDomain::new(&id).chain_err(|| ErrorKind::SigningCertMissingSpiffeId)
+}
+
+/// Checks that `cert` is a CA allowed to sign certificates. Unlike `validate_signing_certificate`,
+/// does not require a SPIFFE ID, which intermediates issued outside SPIFFE tooling often lack.
+fn validate_signing_authority(cert: &OpenSSlX509CertRef) -> Result<()> {
+    let (is_ca, key_usage) = extension_flags(cert);
+
+    if !is_ca {
+        return Err(ErrorKind::SigningCertNotCA.into());
+    }
+
+    match key_usage {
+        Some(key_usage) if key_usage & openssl_sys::X509v3_KU_KEY_CERT_SIGN != 0 => Ok(()),
+        _ => Err(ErrorKind::SigningCertMissingKeyCertSign.into()),
+    }
+}
+
+/// Whether basic constraints mark `cert` as a CA, and its key usage bits if the extension is
+/// present. The openssl crate does not expose either, so they are read through OpenSSL's cached
+/// extension flags.
+fn extension_flags(cert: &OpenSSlX509CertRef) -> (bool, Option<u32>) {
+    // Safe: both functions only read from the certificate, and `cert` outlives the calls
+    unsafe {
+        let flags = openssl_sys::X509_get_extension_flags(cert.as_ptr());
+        let key_usage = if flags & openssl_sys::EXFLAG_KUSAGE != 0 {
+            Some(openssl_sys::X509_get_key_usage(cert.as_ptr()))
+        } else {
+            None
+        };
+        (flags & openssl_sys::EXFLAG_CA != 0, key_usage)
+    }
+}
+
+/// Makes OpenSSL allocate private key components from a secure heap of `size` bytes, locked in
+/// memory and excluded from core dumps, in blocks of at least `min_size` bytes. Both must be
+/// powers of two. Keys parsed before this is called are not moved, and the heap can only be
+/// initialized once per process.
+///
+/// Locking is best effort: if `RLIMIT_MEMLOCK` is too low, the heap is used unlocked.
+#[cfg(all(feature = "mlock", target_os = "linux"))]
+pub fn init_secure_heap(size: usize, min_size: usize) -> Result<()> {
+    if secure::lock::init_secure_heap(size, min_size) {
+        Ok(())
+    } else {
+        Err(ErrorKind::SecureHeapUnavailable.into())
+    }
+}
+
+/// Parses a private key, detecting its format: PKCS#8, SEC1 EC or PKCS#1 RSA, each either PEM or
+/// ASN.1 DER encoded. Encrypted keys are rejected.
+pub fn private_key_from_bytes(key: &[u8]) -> Result<PKey<Private>> {
+    // PEM labels tell the formats apart, and OpenSSL reads all three from PEM. Without a callback
+    // OpenSSL would prompt on the terminal for the passphrase of an encrypted key, so give it an
+    // empty one instead. Keys read from files or the environment often start with blank lines.
+    let start = key
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(key.len());
+    if key[start..].starts_with(b"-----BEGIN") {
+        return PKey::private_key_from_pem_callback(&key[start..], |_| Ok(0))
+            .chain_err(|| ErrorKind::InvalidKey);
+    }
+
+    // DER has no label, so try the formats in turn, the one the Workload API uses first
+    PKey::private_key_from_pkcs8(key)
+        .or_else(|_| EcKey::private_key_from_der(key).and_then(PKey::from_ec_key))
+        .or_else(|_| Rsa::private_key_from_der(key).and_then(PKey::from_rsa))
+        .chain_err(|| ErrorKind::InvalidKey)
+}
+
+/// Parses one or more PEM certificates, in order.
+pub fn certificates_from_pem(pem: &[u8]) -> Result<Vec<OpenSSlX509Cert>> {
+    let certs = OpenSSlX509Cert::stack_from_pem(pem).chain_err(|| ErrorKind::InvalidPEM)?;
+    if certs.is_empty() {
+        return Err(ErrorKind::InvalidPEM.into());
+    }
+    Ok(certs)
+}
+
+/// Parses ASN.1 DER encoded certificates concatenated back to back, e.g. a certificate chain or
+/// bundle from the Workload API.
+pub fn certificates_from_der(der: &[u8]) -> Result<Vec<OpenSSlX509Cert>> {
+    let mut certs = Vec::new();
+    let mut rest = der;
+
+    while !rest.is_empty() {
+        let len = der_element_len(rest).ok_or_else(|| Error::from(ErrorKind::InvalidDER))?;
+        let cert = OpenSSlX509Cert::from_der(&rest[..len]).chain_err(|| ErrorKind::InvalidDER)?;
+        certs.push(cert);
+        rest = &rest[len..];
+    }
+
+    Ok(certs)
+}
+
+/// Length of the DER SEQUENCE at the start of `der`, including its tag and length octets.
+fn der_element_len(der: &[u8]) -> Option<usize> {
+    const SEQUENCE_TAG: u8 = 0x30;
+
+    if der.len() < 2 || der[0] != SEQUENCE_TAG {
+        return None;
+    }
+
+    let (header_len, content_len) = if der[1] & 0x80 == 0 {
+        (2, usize::from(der[1]))
+    } else {
+        let octets = usize::from(der[1] & 0x7f);
+        if octets == 0 || octets > 4 || der.len() < 2 + octets {
+            return None;
+        }
+        let len = der[2..2 + octets]
+            .iter()
+            .fold(0usize, |len, b| (len << 8) | usize::from(*b));
+        (2 + octets, len)
+    };
+
+    let total = header_len.checked_add(content_len)?;
+    if total > der.len() {
+        return None;
+    }
+    Some(total)
+}
+
+fn verification_error(result: X509VerifyResult, depth: u32) -> ErrorKind {
+    match result.as_raw() {
+        X509_V_ERR_CERT_HAS_EXPIRED => ErrorKind::CertificateExpired(depth),
+        X509_V_ERR_CERT_NOT_YET_VALID => ErrorKind::CertificateNotYetValid(depth),
+        X509_V_ERR_CERT_SIGNATURE_FAILURE => ErrorKind::SignatureFailure(depth),
+        X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT
+        | X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY
+        | X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE
+        | X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT
+        | X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN => ErrorKind::UnknownAuthority,
+        _ => ErrorKind::VerificationFailed(depth, result.error_string().to_string()),
+    }
+}
@@ -0,0 +1,283 @@
+use crate::bundle::jwk::{base64url_encode, padded};
+use crate::bundle::jwt::JwtBundle;
+use crate::bundle::spiffe::SpiffeBundle;
+use crate::bundle::x509::X509Bundle;
+use crate::svid::jwt::{ErrorKind, Jwt, Result};
+use crate::svid::SVID;
+use crate::testing::{unix_time, KeyType};
+use crate::uri::TrustDomain;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Padding;
+use openssl::sign::{self, RsaPssSaltlen};
+use serde_json::{Map, Value};
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_JWT_LIFETIME: Duration = Duration::from_secs(5 * 60);
+
+/// The JWS algorithms the `Signer` can sign with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    ES256,
+    ES384,
+    ES512,
+    RS256,
+    RS384,
+    RS512,
+    PS256,
+    PS384,
+    PS512,
+}
+
+impl Algorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::ES256 => "ES256",
+            Algorithm::ES384 => "ES384",
+            Algorithm::ES512 => "ES512",
+            Algorithm::RS256 => "RS256",
+            Algorithm::RS384 => "RS384",
+            Algorithm::RS512 => "RS512",
+            Algorithm::PS256 => "PS256",
+            Algorithm::PS384 => "PS384",
+            Algorithm::PS512 => "PS512",
+        }
+    }
+
+    fn key_type(self) -> KeyType {
+        match self {
+            Algorithm::ES256 => KeyType::EcP256,
+            Algorithm::ES384 => KeyType::EcP384,
+            Algorithm::ES512 => KeyType::EcP521,
+            _ => KeyType::Rsa2048,
+        }
+    }
+
+    fn digest(self) -> MessageDigest {
+        match self {
+            Algorithm::ES256 | Algorithm::RS256 | Algorithm::PS256 => MessageDigest::sha256(),
+            Algorithm::ES384 | Algorithm::RS384 | Algorithm::PS384 => MessageDigest::sha384(),
+            Algorithm::ES512 | Algorithm::RS512 | Algorithm::PS512 => MessageDigest::sha512(),
+        }
+    }
+}
+
+/// Deviations from the JWT-SVID format, for testing that tokens are rejected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Quirk {
+    /// Puts another `alg` in the header, while still signing with the key's algorithm.
+    Alg(String),
+    /// Leaves `kid` out of the header.
+    NoKid,
+    /// Leaves `exp` out of the claims.
+    NoExp,
+    /// Corrupts the signature.
+    BadSignature,
+}
+
+/// What the `Signer` puts in a JWT-SVID.
+#[derive(Clone, Debug)]
+pub struct JWTTemplate {
+    subject: String,
+    audience: Vec<String>,
+    issued_at: Option<SystemTime>,
+    expiry: Option<SystemTime>,
+    claims: Map<String, Value>,
+    key_id: Option<String>,
+    quirks: Vec<Quirk>,
+}
+
+impl JWTTemplate {
+    /// A token for `subject` and `audience`, issued now and expiring in 5 minutes, signed with the
+    /// signer's first key. The subject is not checked, so invalid SPIFFE IDs can be used too.
+    pub fn new(subject: &str, audience: &[&str]) -> JWTTemplate {
+        JWTTemplate {
+            subject: subject.to_string(),
+            audience: audience.iter().map(|aud| aud.to_string()).collect(),
+            issued_at: None,
+            expiry: None,
+            claims: Map::new(),
+            key_id: None,
+            quirks: Vec::new(),
+        }
+    }
+
+    pub fn issued_at(mut self, issued_at: SystemTime) -> JWTTemplate {
+        self.issued_at = Some(issued_at);
+        self
+    }
+
+    /// Sets `exp`, for instance in the past to mint an expired token.
+    pub fn expiry(mut self, expiry: SystemTime) -> JWTTemplate {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Adds a claim, replacing the registered claims above if `name` is one of them.
+    pub fn claim(mut self, name: &str, value: Value) -> JWTTemplate {
+        self.claims.insert(name.to_string(), value);
+        self
+    }
+
+    /// Signs with the key of `key_id`, which need not be one the signer has.
+    pub fn key_id(mut self, key_id: &str) -> JWTTemplate {
+        self.key_id = Some(key_id.to_string());
+        self
+    }
+
+    pub fn quirk(mut self, quirk: Quirk) -> JWTTemplate {
+        self.quirks.push(quirk);
+        self
+    }
+
+    fn has(&self, quirk: &Quirk) -> bool {
+        self.quirks.contains(quirk)
+    }
+}
+
+struct SigningKey {
+    key_id: String,
+    algorithm: Algorithm,
+    key: PKey<Private>,
+}
+
+/// A JWT authority for a trust domain that mints JWT-SVIDs and publishes its keys as a bundle.
+pub struct Signer {
+    trust_domain: TrustDomain,
+    keys: Vec<SigningKey>,
+}
+
+impl Signer {
+    /// A signer with no keys yet.
+    pub fn new(trust_domain: TrustDomain) -> Signer {
+        Signer {
+            trust_domain,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    /// Generates a key for `algorithm` under `key_id`, replacing any key with the same ID.
+    pub fn generate_key(&mut self, key_id: &str, algorithm: Algorithm) -> Result<()> {
+        let key = algorithm.key_type().generate()?;
+        self.keys.retain(|k| k.key_id != key_id);
+        self.keys.push(SigningKey {
+            key_id: key_id.to_string(),
+            algorithm,
+            key,
+        });
+        Ok(())
+    }
+
+    /// Forgets the key of `key_id`, so tokens it signed no longer validate against new bundles.
+    pub fn remove_key(&mut self, key_id: &str) -> bool {
+        let len = self.keys.len();
+        self.keys.retain(|k| k.key_id != key_id);
+        self.keys.len() != len
+    }
+
+    /// The bundle of the trust domain, holding the public keys.
+    pub fn bundle(&self) -> Result<JwtBundle> {
+        let mut bundle = JwtBundle::new(self.trust_domain.clone());
+        for key in self.keys.iter() {
+            let public = PKey::public_key_from_der(&key.key.public_key_to_der()?)?;
+            bundle.add_authority(&key.key_id, public);
+        }
+        Ok(bundle)
+    }
+
+    /// The bundle as a JWK Set in the SPIFFE bundle format.
+    pub fn jwks(&self) -> Result<String> {
+        let bundle =
+            SpiffeBundle::from_bundles(X509Bundle::new(self.trust_domain.clone()), self.bundle()?)?;
+        Ok(bundle.to_json()?)
+    }
+
+    /// Mints a JWT-SVID from `template`. Templates with quirks fail as `SVID::<Jwt>::new` would
+    /// reject them; see `sign` to get the token.
+    pub fn issue(&self, template: &JWTTemplate) -> Result<SVID<Jwt>> {
+        SVID::<Jwt>::new(self.sign(template)?)
+    }
+
+    /// Mints a token from `template`. Only the key ID is checked, so templates with quirks or
+    /// invalid claims are signed as they are.
+    pub fn sign(&self, template: &JWTTemplate) -> Result<String> {
+        let signing_key = match &template.key_id {
+            Some(key_id) => self.keys.iter().find(|k| &k.key_id == key_id),
+            None => self.keys.first(),
+        }
+        .ok_or_else(|| ErrorKind::UnknownKeyId(template.key_id.clone().unwrap_or_default()))?;
+
+        let mut header = Map::new();
+        let alg = template
+            .quirks
+            .iter()
+            .find_map(|quirk| match quirk {
+                Quirk::Alg(alg) => Some(alg.as_str()),
+                _ => None,
+            })
+            .unwrap_or_else(|| signing_key.algorithm.name());
+        header.insert("alg".to_string(), alg.into());
+        header.insert("typ".to_string(), "JWT".into());
+        if !template.has(&Quirk::NoKid) {
+            header.insert("kid".to_string(), signing_key.key_id.as_str().into());
+        }
+
+        let issued_at = template.issued_at.unwrap_or_else(SystemTime::now);
+        let expiry = template
+            .expiry
+            .unwrap_or_else(|| issued_at + DEFAULT_JWT_LIFETIME);
+        let mut claims = Map::new();
+        claims.insert("sub".to_string(), template.subject.as_str().into());
+        claims.insert("aud".to_string(), template.audience.clone().into());
+        claims.insert("iat".to_string(), unix_time(issued_at).into());
+        if !template.has(&Quirk::NoExp) {
+            claims.insert("exp".to_string(), unix_time(expiry).into());
+        }
+        claims.extend(template.claims.clone());
+
+        let signing_input = format!(
+            "{}.{}",
+            base64url_encode(&serde_json::to_vec(&header)?),
+            base64url_encode(&serde_json::to_vec(&claims)?)
+        );
+        let mut signature = signature(signing_key, signing_input.as_bytes())?;
+        if template.has(&Quirk::BadSignature) {
+            signature[0] ^= 0xff;
+        }
+
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            base64url_encode(&signature)
+        ))
+    }
+}
+
+fn signature(signing_key: &SigningKey, input: &[u8]) -> Result<Vec<u8>> {
+    let algorithm = signing_key.algorithm;
+    let mut signer = sign::Signer::new(algorithm.digest(), &signing_key.key)?;
+    if let Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 = algorithm {
+        signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+        signer.set_rsa_pss_saltlen(RsaPssSaltlen::DIGEST_LENGTH)?;
+        signer.set_rsa_mgf1_md(algorithm.digest())?;
+    }
+    signer.update(input)?;
+    let signature = signer.sign_to_vec()?;
+
+    // JWS uses the fixed-size concatenation of r and s rather than DER (RFC 7518, 3.4)
+    let len = match algorithm {
+        Algorithm::ES256 => 32,
+        Algorithm::ES384 => 48,
+        Algorithm::ES512 => 66,
+        _ => return Ok(signature),
+    };
+    let signature = EcdsaSig::from_der(&signature)?;
+    let mut raw = padded(signature.r(), len);
+    raw.extend(padded(signature.s(), len));
+    Ok(raw)
+}
@@ -0,0 +1,51 @@
+//! In-process authorities that mint SVIDs and bundles for tests, so they need neither a SPIRE
+//! agent nor certificates pasted into the test sources. Enabled by the `testing` feature.
+
+pub mod jwt;
+pub mod x509;
+
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The type of key generated for an authority or SVID.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyType {
+    EcP256,
+    EcP384,
+    EcP521,
+    Rsa2048,
+}
+
+impl Default for KeyType {
+    fn default() -> KeyType {
+        KeyType::EcP256
+    }
+}
+
+impl KeyType {
+    pub fn generate(self) -> Result<PKey<Private>, ErrorStack> {
+        match self {
+            KeyType::EcP256 => ec_key(Nid::X9_62_PRIME256V1),
+            KeyType::EcP384 => ec_key(Nid::SECP384R1),
+            KeyType::EcP521 => ec_key(Nid::SECP521R1),
+            KeyType::Rsa2048 => PKey::from_rsa(Rsa::generate(2048)?),
+        }
+    }
+}
+
+fn ec_key(nid: Nid) -> Result<PKey<Private>, ErrorStack> {
+    let group = EcGroup::from_curve_name(nid)?;
+    PKey::from_ec_key(EcKey::generate(&group)?)
+}
+
+/// Seconds since the Unix epoch, negative before it.
+pub(crate) fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
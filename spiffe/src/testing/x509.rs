@@ -0,0 +1,310 @@
+use crate::bundle::x509::X509Bundle;
+use crate::svid::x509::{ErrorKind, Result, X509};
+use crate::svid::SVID;
+use crate::testing::{unix_time, KeyType};
+use crate::uri::TrustDomain;
+use openssl::asn1::{Asn1Integer, Asn1Time};
+use openssl::bn::{BigNum, MsbOption};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, PKeyRef, Private};
+use openssl::x509::extension::{BasicConstraints, KeyUsage, SubjectAlternativeName};
+use openssl::x509::{X509Builder, X509Name};
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime};
+
+type OpenSSlX509Cert = ::openssl::x509::X509;
+
+/// How long authorities are valid for, either side of their creation.
+const AUTHORITY_LIFETIME: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+const DEFAULT_SVID_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// Deviations from the X509-SVID profile, for testing that SVIDs are rejected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Quirk {
+    /// Sets CA to true in the basic constraints.
+    CA,
+    /// Leaves digitalSignature out of the key usage.
+    NoDigitalSignature,
+    /// Adds keyCertSign to the key usage.
+    KeyCertSign,
+    /// Adds cRLSign to the key usage.
+    CRLSign,
+    /// Leaves out the subject alternative names, including the SPIFFE ID.
+    NoSAN,
+    /// Adds a URI SAN after the SPIFFE ID.
+    ExtraURI(String),
+}
+
+/// What the `CA` puts in an X.509-SVID.
+#[derive(Clone, Debug)]
+pub struct SVIDTemplate {
+    spiffe_id: String,
+    key_type: KeyType,
+    not_before: Option<SystemTime>,
+    lifetime: Duration,
+    quirks: Vec<Quirk>,
+}
+
+impl SVIDTemplate {
+    /// An SVID for `spiffe_id`, valid for an hour from now, with a P-256 key. The ID is not
+    /// checked, so invalid ones can be issued too.
+    pub fn new(spiffe_id: &str) -> SVIDTemplate {
+        SVIDTemplate {
+            spiffe_id: spiffe_id.to_string(),
+            key_type: KeyType::default(),
+            not_before: None,
+            lifetime: DEFAULT_SVID_LIFETIME,
+            quirks: Vec::new(),
+        }
+    }
+
+    pub fn key_type(mut self, key_type: KeyType) -> SVIDTemplate {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Starts the validity period at `not_before` rather than now, for instance in the past to
+    /// issue an expired SVID.
+    pub fn not_before(mut self, not_before: SystemTime) -> SVIDTemplate {
+        self.not_before = Some(not_before);
+        self
+    }
+
+    pub fn lifetime(mut self, lifetime: Duration) -> SVIDTemplate {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn quirk(mut self, quirk: Quirk) -> SVIDTemplate {
+        self.quirks.push(quirk);
+        self
+    }
+
+    fn has(&self, quirk: &Quirk) -> bool {
+        self.quirks.contains(quirk)
+    }
+}
+
+struct Authority {
+    cert: OpenSSlX509Cert,
+    key: PKey<Private>,
+}
+
+/// A certificate authority for a trust domain: a root, optionally followed by a chain of
+/// intermediates that issue the SVIDs.
+pub struct CA {
+    trust_domain: TrustDomain,
+    // Root first
+    authorities: Vec<Authority>,
+}
+
+impl CA {
+    /// A CA whose root issues the SVIDs.
+    pub fn new(trust_domain: TrustDomain) -> Result<CA> {
+        CA::with_intermediates(trust_domain, 0)
+    }
+
+    /// A CA with `count` intermediates between the root and the SVIDs.
+    pub fn with_intermediates(trust_domain: TrustDomain, count: usize) -> Result<CA> {
+        let mut authorities: Vec<Authority> = Vec::with_capacity(count + 1);
+        // Distinguishes the names of CAs of the same trust domain, as OpenSSL finds issuers by name
+        let id = serial()?.to_bn()?.to_hex_str()?.to_string();
+
+        for depth in 0..=count {
+            let key = KeyType::default().generate()?;
+            let name = if depth == 0 {
+                format!("{} Root CA {}", trust_domain, id)
+            } else {
+                format!("{} Intermediate CA {} {}", trust_domain, id, depth)
+            };
+
+            let now = SystemTime::now();
+            let mut builder = builder(
+                &name,
+                &key,
+                now - AUTHORITY_LIFETIME,
+                now + AUTHORITY_LIFETIME,
+            )?;
+            builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+            builder.append_extension(
+                KeyUsage::new()
+                    .critical()
+                    .key_cert_sign()
+                    .crl_sign()
+                    .build()?,
+            )?;
+
+            let issuer = authorities.last();
+            let san = SubjectAlternativeName::new()
+                .uri(&format!("spiffe://{}", trust_domain))
+                .build(&builder.x509v3_context(issuer.map(|i| i.cert.as_ref()), None))?;
+            builder.append_extension(san)?;
+
+            let cert = match issuer {
+                Some(issuer) => sign(builder, issuer)?,
+                None => {
+                    let subject = subject_name(&name)?;
+                    builder.set_issuer_name(&subject)?;
+                    builder.sign(&key, MessageDigest::sha256())?;
+                    builder.build()
+                }
+            };
+
+            authorities.push(Authority { cert, key });
+        }
+
+        Ok(CA {
+            trust_domain,
+            authorities,
+        })
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn root(&self) -> &OpenSSlX509Cert {
+        &self.authorities[0].cert
+    }
+
+    /// The intermediates presented after an SVID, in chain order.
+    pub fn intermediates(&self) -> Vec<OpenSSlX509Cert> {
+        self.authorities[1..]
+            .iter()
+            .rev()
+            .map(|authority| authority.cert.clone())
+            .collect()
+    }
+
+    /// The bundle of the trust domain, holding the root.
+    pub fn bundle(&self) -> X509Bundle {
+        X509Bundle::from_authorities(self.trust_domain.clone(), vec![self.root().clone()])
+    }
+
+    /// The bundle as the Workload API delivers it, in ASN.1 DER.
+    pub fn bundle_der(&self) -> Result<Vec<u8>> {
+        Ok(self.root().to_der()?)
+    }
+
+    /// Issues an SVID for `spiffe_id` with the defaults of `SVIDTemplate`.
+    pub fn issue(&self, spiffe_id: &str) -> Result<SVID<X509>> {
+        self.issue_with(&SVIDTemplate::new(spiffe_id))
+    }
+
+    /// Issues an SVID from `template`, along with its key and the bundle. Templates with quirks
+    /// fail as the SVID constructors would reject them; see `issue_chain` to get the certificates.
+    pub fn issue_with(&self, template: &SVIDTemplate) -> Result<SVID<X509>> {
+        let (chain, key) = self.issue_chain(template)?;
+        SVID::<X509>::from_x509_chain(
+            chain,
+            Some(key.private_key_to_der()?),
+            Some(self.bundle_der()?),
+        )
+    }
+
+    /// Issues the certificate chain of an SVID from `template`, leaf first, and the leaf's key.
+    /// Nothing is checked, so this also works for templates that break the SVID profile.
+    pub fn issue_chain(
+        &self,
+        template: &SVIDTemplate,
+    ) -> Result<(Vec<OpenSSlX509Cert>, PKey<Private>)> {
+        let key = template.key_type.generate()?;
+        let not_before = template.not_before.unwrap_or_else(SystemTime::now);
+        let mut builder = builder("SVID", &key, not_before, not_before + template.lifetime)?;
+
+        let mut constraints = BasicConstraints::new();
+        constraints.critical();
+        if template.has(&Quirk::CA) {
+            constraints.ca();
+        }
+        builder.append_extension(constraints.build()?)?;
+
+        let mut usage = KeyUsage::new();
+        usage.critical();
+        if template.has(&Quirk::NoDigitalSignature) {
+            // An empty key usage cannot be encoded
+            usage.key_encipherment();
+        } else {
+            usage.digital_signature();
+        }
+        if template.has(&Quirk::KeyCertSign) {
+            usage.key_cert_sign();
+        }
+        if template.has(&Quirk::CRLSign) {
+            usage.crl_sign();
+        }
+        builder.append_extension(usage.build()?)?;
+
+        let issuer = self.issuer();
+        if !template.has(&Quirk::NoSAN) {
+            let mut san = SubjectAlternativeName::new();
+            san.uri(&template.spiffe_id);
+            for quirk in template.quirks.iter() {
+                if let Quirk::ExtraURI(uri) = quirk {
+                    san.uri(uri);
+                }
+            }
+            let san = san.build(&builder.x509v3_context(Some(&issuer.cert), None))?;
+            builder.append_extension(san)?;
+        }
+
+        let mut chain = vec![sign(builder, issuer)?];
+        chain.extend(self.intermediates());
+        Ok((chain, key))
+    }
+
+    fn issuer(&self) -> &Authority {
+        // Never empty, the root is created first
+        &self.authorities[self.authorities.len() - 1]
+    }
+}
+
+fn builder(
+    common_name: &str,
+    key: &PKeyRef<Private>,
+    not_before: SystemTime,
+    not_after: SystemTime,
+) -> Result<X509Builder> {
+    let mut builder = X509Builder::new()?;
+    builder.set_version(2)?;
+    let serial = serial()?;
+    builder.set_serial_number(&serial)?;
+    let subject = subject_name(common_name)?;
+    builder.set_subject_name(&subject)?;
+    let not_before = asn1_time(not_before)?;
+    builder.set_not_before(&not_before)?;
+    let not_after = asn1_time(not_after)?;
+    builder.set_not_after(&not_after)?;
+    builder.set_pubkey(key)?;
+    Ok(builder)
+}
+
+/// `time_t` is only 32 bits wide on some targets.
+fn asn1_time(time: SystemTime) -> Result<Asn1Time> {
+    let time = unix_time(time);
+    let time = libc::time_t::try_from(time).map_err(|_| ErrorKind::ValidityOutOfRange(time))?;
+    Ok(Asn1Time::from_unix(time)?)
+}
+
+fn sign(
+    mut builder: X509Builder,
+    issuer: &Authority,
+) -> std::result::Result<OpenSSlX509Cert, ErrorStack> {
+    builder.set_issuer_name(issuer.cert.subject_name())?;
+    builder.sign(&issuer.key, MessageDigest::sha256())?;
+    Ok(builder.build())
+}
+
+fn subject_name(common_name: &str) -> std::result::Result<X509Name, ErrorStack> {
+    let mut name = X509Name::builder()?;
+    name.append_entry_by_text("O", "SPIFFE")?;
+    name.append_entry_by_text("CN", common_name)?;
+    Ok(name.build())
+}
+
+fn serial() -> std::result::Result<Asn1Integer, ErrorStack> {
+    let mut serial = BigNum::new()?;
+    serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+    serial.to_asn1_integer()
+}
@@ -0,0 +1,109 @@
+use crate::uri::parser::{self, SPIFFE_SCHEME_PREFIX};
+use crate::uri::{Result, TrustDomain, URI};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::Split;
+
+/// A validated SPIFFE ID borrowed from a string slice.
+///
+/// Parsing validates the input in place with the same grammar as `URI` and does not allocate, so
+/// it is suited to hot paths such as checking the ID of every incoming peer. Use `to_uri` (or
+/// `URI::from`) to take ownership, and `URI::as_id_ref` to go the other way.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SpiffeIdRef<'a> {
+    id: &'a str,
+    path_start: usize,
+}
+
+impl<'a> SpiffeIdRef<'a> {
+    pub fn new(id: &'a str) -> Result<SpiffeIdRef<'a>> {
+        let path_start = parser::parse_workload_id(id)?;
+        Ok(SpiffeIdRef { id, path_start })
+    }
+
+    pub(crate) fn from_validated(id: &'a str, path_start: usize) -> SpiffeIdRef<'a> {
+        SpiffeIdRef { id, path_start }
+    }
+
+    /// The full SPIFFE ID, e.g. `spiffe://example.org/service`.
+    pub fn as_str(&self) -> &'a str {
+        self.id
+    }
+
+    /// The trust domain name, e.g. `example.org`.
+    pub fn trust_domain_name(&self) -> &'a str {
+        &self.id[SPIFFE_SCHEME_PREFIX.len()..self.path_start]
+    }
+
+    /// The path including its leading slash, e.g. `/service`.
+    pub fn path(&self) -> &'a str {
+        &self.id[self.path_start..]
+    }
+
+    /// Iterates over the path segments without allocating.
+    pub fn segments(&self) -> Segments<'a> {
+        Segments {
+            inner: self.id[self.path_start + 1..].split('/'),
+        }
+    }
+
+    /// Copies the trust domain name into an owned `TrustDomain`.
+    pub fn trust_domain(&self) -> TrustDomain {
+        TrustDomain::from_validated_name(self.trust_domain_name())
+    }
+
+    pub fn to_uri(&self) -> URI {
+        URI::from(*self)
+    }
+
+    pub(crate) fn path_start(&self) -> usize {
+        self.path_start
+    }
+}
+
+/// Iterator over the path segments of a SPIFFE ID, created by `URI::segments` and
+/// `SpiffeIdRef::segments`.
+#[derive(Clone, Debug)]
+pub struct Segments<'a> {
+    inner: Split<'a, char>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.inner.next()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Segments<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SpiffeIdRef<'a> {
+    type Error = crate::uri::Error;
+
+    fn try_from(id: &'a str) -> Result<SpiffeIdRef<'a>> {
+        SpiffeIdRef::new(id)
+    }
+}
+
+impl<'a> fmt::Display for SpiffeIdRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.id)
+    }
+}
+
+impl<'a> PartialEq<URI> for SpiffeIdRef<'a> {
+    fn eq(&self, other: &URI) -> bool {
+        self.id == other.as_str()
+    }
+}
+
+impl<'a> AsRef<str> for SpiffeIdRef<'a> {
+    fn as_ref(&self) -> &str {
+        self.id
+    }
+}
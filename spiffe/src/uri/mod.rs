@@ -1,6 +1,17 @@
+mod id_ref;
+mod parser;
+mod pattern;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod trust_domain;
+
+pub use self::id_ref::{Segments, SpiffeIdRef};
+pub use self::pattern::{Captures, SpiffeIdPattern};
+pub use self::trust_domain::TrustDomain;
+
 use error_chain::error_chain;
+use std::fmt;
 use std::str::FromStr;
-use std::string::ToString;
 use url::Url;
 
 error_chain! {
@@ -10,6 +21,75 @@ error_chain! {
             display("Unable to parse SVID: Not a valid SPIFFE URI {}", uri)
         }
 
+        WrongScheme(id: String) {
+            description("An error during the parsing of a SPIFFE ID scheme")
+            display("Not a valid SPIFFE ID: scheme is missing or is not spiffe in {}", id)
+        }
+
+        MissingTrustDomain {
+            description("An error during the parsing of a SPIFFE trust domain")
+            display("Not a valid trust domain: trust domain is missing")
+        }
+
+        TrustDomainTooLong(len: usize) {
+            description("An error during the parsing of a SPIFFE trust domain")
+            display("Not a valid trust domain: {} bytes exceeds the maximum of 255", len)
+        }
+
+        UppercaseTrustDomain(td: String) {
+            description("An error during the parsing of a SPIFFE trust domain")
+            display("Not a valid trust domain: {} contains uppercase characters", td)
+        }
+
+        BadTrustDomainChar(td: String) {
+            description("An error during the parsing of a SPIFFE trust domain")
+            display("Not a valid trust domain: {} contains characters other than lowercase letters, digits, dots, dashes and underscores", td)
+        }
+
+        TrustDomainHasPath(id: String) {
+            description("An error during the parsing of a SPIFFE trust domain")
+            display("Not a valid trust domain: SPIFFE ID {} has a path", id)
+        }
+
+        EmptySpiffeId {
+            description("An error during the parsing of a SPIFFE ID")
+            display("Not a valid SPIFFE ID: SPIFFE ID is empty")
+        }
+
+        SpiffeIdTooLong(len: usize) {
+            description("An error during the parsing of a SPIFFE ID")
+            display("Not a valid SPIFFE ID: {} bytes exceeds the maximum of 2048", len)
+        }
+
+        MissingPath(id: String) {
+            description("An error during the parsing of a SPIFFE ID path")
+            display("Not a valid workload SPIFFE ID: {} has no path", id)
+        }
+
+        EmptySegment(id: String) {
+            description("An error during the parsing of a SPIFFE ID path")
+            display("Not a valid SPIFFE ID: {} contains an empty path segment", id)
+        }
+
+        DotSegment(id: String) {
+            description("An error during the parsing of a SPIFFE ID path")
+            display("Not a valid SPIFFE ID: {} contains a dot segment", id)
+        }
+
+        BadPathSegmentChar(id: String) {
+            description("An error during the parsing of a SPIFFE ID path")
+            display("Not a valid SPIFFE ID: {} contains path characters other than letters, digits, dots, dashes and underscores", id)
+        }
+
+        TrailingSlash(id: String) {
+            description("An error during the parsing of a SPIFFE ID path")
+            display("Not a valid SPIFFE ID: {} has a trailing slash", id)
+        }
+
+        InvalidPattern(pattern: String, reason: String) {
+            description("An error during the parsing of a SPIFFE ID pattern")
+            display("Not a valid SPIFFE ID pattern: {}: {}", pattern, reason)
+        }
     }
 }
 
@@ -19,42 +99,124 @@ impl<'a> From<&'a Url> for ErrorKind {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct URI {
-    uri: Url,
+    id: String,
+    path_start: usize,
+    trust_domain: TrustDomain,
 }
 
 impl URI {
-    pub fn path(&self) -> String {
-        self.uri.path().to_string()
+    pub fn path(&self) -> &str {
+        &self.id[self.path_start..]
+    }
+
+    pub fn trust_domain(&self) -> &TrustDomain {
+        &self.trust_domain
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
     }
 
-    pub fn trust_domain(&self) -> String {
-        // This unwrap should never fail -- if it does, something is wrong and please file bug
-        self.uri.host().unwrap().to_string()
+    /// Iterates over the path segments, e.g. `ns`, `prod`, `sa` and `api` for
+    /// `spiffe://example.org/ns/prod/sa/api`. Segments are never empty and never escaped.
+    pub fn segments(&self) -> Segments<'_> {
+        self.as_id_ref().segments()
     }
 
+    /// Builds a SPIFFE ID from a trust domain and path segments, validating each segment.
+    pub fn from_segments<S: AsRef<str>>(trust_domain: &TrustDomain, segments: &[S]) -> Result<URI> {
+        let mut id = trust_domain.id_string();
+        let path_start = id.len();
+
+        if segments.is_empty() {
+            return Err(ErrorKind::MissingPath(id).into());
+        }
+
+        for segment in segments {
+            URI::push_segment(&mut id, segment.as_ref())?;
+        }
+
+        URI::from_parts(id, path_start, trust_domain.clone())
+    }
+
+    /// The SPIFFE ID with the last path segment removed, or `None` if only one segment remains.
+    pub fn parent(&self) -> Option<URI> {
+        let last_slash = self.id.rfind('/')?;
+        if last_slash == self.path_start {
+            return None;
+        }
+
+        Some(URI {
+            id: self.id[..last_slash].to_string(),
+            path_start: self.path_start,
+            trust_domain: self.trust_domain.clone(),
+        })
+    }
+
+    /// The SPIFFE ID with `segment` appended to the path.
+    pub fn join(&self, segment: &str) -> Result<URI> {
+        let mut id = self.id.clone();
+        URI::push_segment(&mut id, segment)?;
+        URI::from_parts(id, self.path_start, self.trust_domain.clone())
+    }
+
+    /// Whether `self` lies strictly below `ancestor` in the same trust domain, comparing whole
+    /// path segments: `spiffe://example.org/a/b` descends from `spiffe://example.org/a` but
+    /// `spiffe://example.org/ab` does not.
+    pub fn is_descendant_of(&self, ancestor: &URI) -> bool {
+        self.id.len() > ancestor.id.len()
+            && self.id.starts_with(&ancestor.id)
+            && self.id.as_bytes()[ancestor.id.len()] == b'/'
+    }
+
+    /// Borrows this SPIFFE ID without copying it.
+    pub fn as_id_ref(&self) -> SpiffeIdRef<'_> {
+        SpiffeIdRef::from_validated(&self.id, self.path_start)
+    }
+
+    /// Validates a parsed URL against the SPIFFE ID specification. Percent-encoding, empty segments
+    /// and trailing slashes are rejected, but dot segments are not: `Url` has already resolved
+    /// them while parsing, so `spiffe://example.org/a/../b` arrives as `spiffe://example.org/b`.
+    /// Use `URI::from_str` on the original string to reject them too.
     pub fn validate_spiffe_uri(uri: Url) -> Result<Url> {
-        if uri.query().is_some()
-            || uri.port().is_some()
-            || (uri.username() != "")
-            || uri.password().is_some()
-            || uri.fragment().is_some()
-            || uri.host().is_none()
-            || (uri.scheme() != "spiffe")
-            || (uri.path() == "/")
-            || (uri.path() == "")
-        {
-            Err(ErrorKind::from(&uri).into())
-        } else {
-            Ok(uri)
+        parser::parse_workload_id(uri.as_str())?;
+        Ok(uri)
+    }
+
+    fn push_segment(id: &mut String, segment: &str) -> Result<()> {
+        id.push('/');
+        id.push_str(segment);
+        parser::validate_segment(segment).map_err(|kind| kind.with_id(id).into())
+    }
+
+    fn from_parts(id: String, path_start: usize, trust_domain: TrustDomain) -> Result<URI> {
+        if id.len() > parser::MAX_SPIFFE_ID_LENGTH {
+            return Err(ErrorKind::SpiffeIdTooLong(id.len()).into());
+        }
+
+        Ok(URI {
+            id,
+            path_start,
+            trust_domain,
+        })
+    }
+
+    fn from_validated(id: String, path_start: usize) -> URI {
+        let trust_domain =
+            TrustDomain::from_validated_name(&id[parser::SPIFFE_SCHEME_PREFIX.len()..path_start]);
+        URI {
+            id,
+            path_start,
+            trust_domain,
         }
     }
 }
 
-impl ToString for URI {
-    fn to_string(&self) -> String {
-        self.uri.as_str().to_string()
+impl fmt::Display for URI {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.id)
     }
 }
 
@@ -62,12 +224,19 @@ impl FromStr for URI {
     type Err = Error;
 
     fn from_str(uri: &str) -> Result<URI> {
-        match uri.parse::<Url>() {
-            Ok(uri) => match URI::validate_spiffe_uri(uri) {
-                Ok(validated_uri) => Ok(URI { uri: validated_uri }),
-                Err(e) => Err(e),
-            },
-            Err(_) => Err(ErrorKind::InvalidURI(uri.to_string()).into()),
-        }
+        let path_start = parser::parse_workload_id(uri)?;
+        Ok(URI::from_validated(uri.to_string(), path_start))
+    }
+}
+
+impl<'a> From<SpiffeIdRef<'a>> for URI {
+    fn from(id: SpiffeIdRef<'a>) -> URI {
+        URI::from_validated(id.as_str().to_string(), id.path_start())
+    }
+}
+
+impl<'a> PartialEq<SpiffeIdRef<'a>> for URI {
+    fn eq(&self, other: &SpiffeIdRef<'a>) -> bool {
+        self.id == other.as_str()
     }
 }
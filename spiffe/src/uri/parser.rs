@@ -0,0 +1,110 @@
+//! Hand-written SPIFFE ID grammar following the SPIFFE ID specification, so that IDs accepted here
+//! are exactly the ones accepted by the other SPIFFE libraries.
+//!
+//! ```text
+//! spiffe-id    = "spiffe://" trust-domain *( "/" path-segment )
+//! trust-domain = 1*255( %x61-7A / DIGIT / "." / "-" / "_" )
+//! path-segment = 1*( ALPHA / DIGIT / "." / "-" / "_" ), other than "." and ".."
+//! ```
+
+use crate::uri::{ErrorKind, Result, TrustDomain};
+
+pub(crate) const SPIFFE_SCHEME_PREFIX: &str = "spiffe://";
+pub(crate) const MAX_SPIFFE_ID_LENGTH: usize = 2048;
+
+/// Validates `id` in place and returns the byte offset at which its path starts. The path is empty
+/// for the ID of a trust domain. Nothing is allocated unless `id` is rejected.
+pub(crate) fn parse_spiffe_id(id: &str) -> Result<usize> {
+    if id.is_empty() {
+        return Err(ErrorKind::EmptySpiffeId.into());
+    }
+
+    if id.len() > MAX_SPIFFE_ID_LENGTH {
+        return Err(ErrorKind::SpiffeIdTooLong(id.len()).into());
+    }
+
+    if !id.starts_with(SPIFFE_SCHEME_PREFIX) {
+        return Err(ErrorKind::WrongScheme(id.to_string()).into());
+    }
+
+    let rest = &id[SPIFFE_SCHEME_PREFIX.len()..];
+    let td_len = rest.find('/').unwrap_or(rest.len());
+    TrustDomain::validate_name(&rest[..td_len])?;
+
+    let path_start = SPIFFE_SCHEME_PREFIX.len() + td_len;
+    validate_path(&id[path_start..]).map_err(|kind| kind.with_id(id))?;
+
+    Ok(path_start)
+}
+
+/// Like `parse_spiffe_id`, but additionally rejects the ID of a trust domain, which does not
+/// identify a workload.
+pub(crate) fn parse_workload_id(id: &str) -> Result<usize> {
+    let path_start = parse_spiffe_id(id)?;
+    if path_start == id.len() {
+        return Err(ErrorKind::MissingPath(id.to_string()).into());
+    }
+    Ok(path_start)
+}
+
+/// Validates a single path segment, i.e. a non-empty run of allowed characters other than `.`
+/// and `..`.
+pub(crate) fn validate_segment(segment: &str) -> std::result::Result<(), PathError> {
+    if segment.is_empty() {
+        return Err(PathError::EmptySegment);
+    }
+
+    if segment == "." || segment == ".." {
+        return Err(PathError::DotSegment);
+    }
+
+    if !segment.bytes().all(is_valid_path_char) {
+        return Err(PathError::BadChar);
+    }
+
+    Ok(())
+}
+
+fn validate_path(path: &str) -> std::result::Result<(), PathError> {
+    if path.is_empty() {
+        return Ok(());
+    }
+
+    // The trust domain ends at the first slash, so a non-empty path always starts with one
+    let mut segments = path[1..].split('/').peekable();
+    while let Some(segment) = segments.next() {
+        match validate_segment(segment) {
+            Err(PathError::EmptySegment) if segments.peek().is_none() => {
+                return Err(PathError::TrailingSlash)
+            }
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
+fn is_valid_path_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'.' || c == b'-' || c == b'_'
+}
+
+/// Path rejections without the offending ID attached, so segment validation stays allocation-free.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PathError {
+    EmptySegment,
+    DotSegment,
+    BadChar,
+    TrailingSlash,
+}
+
+impl PathError {
+    pub(crate) fn with_id(self, id: &str) -> ErrorKind {
+        let id = id.to_string();
+        match self {
+            PathError::EmptySegment => ErrorKind::EmptySegment(id),
+            PathError::DotSegment => ErrorKind::DotSegment(id),
+            PathError::BadChar => ErrorKind::BadPathSegmentChar(id),
+            PathError::TrailingSlash => ErrorKind::TrailingSlash(id),
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::uri::parser::{self, SPIFFE_SCHEME_PREFIX};
+use crate::uri::{Error, ErrorKind, Result, SpiffeIdRef, TrustDomain, URI};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A pattern over SPIFFE IDs, e.g. `spiffe://prod.acme.com/ns/{namespace}/sa/*`.
+///
+/// The trust domain is matched label by label and the path segment by segment, where each label or
+/// segment of the pattern is one of:
+///
+/// * a literal, matched exactly;
+/// * `*`, matching any single label or segment;
+/// * `{name}`, matching any single label or segment and capturing it under `name`;
+/// * `**` (path only), matching any number of segments, including none.
+///
+/// So `spiffe://*.acme.com/**` matches every ID in any direct subdomain of `acme.com`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpiffeIdPattern {
+    pattern: String,
+    trust_domain: Vec<Part>,
+    path: Vec<Part>,
+    capture_names: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Part {
+    Literal(String),
+    Wildcard,
+    Capture(usize),
+    Recursive,
+}
+
+/// Values captured by the `{name}` parts of a `SpiffeIdPattern`, borrowed from the matched ID.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Captures<'a> {
+    values: HashMap<String, &'a str>,
+}
+
+impl<'a> Captures<'a> {
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.values.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &'a str)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+impl SpiffeIdPattern {
+    pub fn new(pattern: &str) -> Result<SpiffeIdPattern> {
+        let invalid = |reason: &str| -> Error {
+            ErrorKind::InvalidPattern(pattern.to_string(), reason.to_string()).into()
+        };
+
+        if !pattern.starts_with(SPIFFE_SCHEME_PREFIX) {
+            return Err(invalid("scheme is missing or is not spiffe"));
+        }
+
+        let rest = &pattern[SPIFFE_SCHEME_PREFIX.len()..];
+        let (td, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => return Err(invalid("path is missing")),
+        };
+
+        let mut capture_names = Vec::new();
+
+        let mut trust_domain = Vec::new();
+        for label in td.split('.') {
+            let part = SpiffeIdPattern::parse_part(label, &mut capture_names).map_err(invalid)?;
+            match part {
+                Part::Recursive => return Err(invalid("** is only allowed in the path")),
+                Part::Literal(ref l) if TrustDomain::validate_name(l).is_err() => {
+                    return Err(invalid("trust domain label is not valid"))
+                }
+                _ => trust_domain.push(part),
+            }
+        }
+
+        let mut path_parts = Vec::new();
+        for segment in path.split('/') {
+            let part = SpiffeIdPattern::parse_part(segment, &mut capture_names).map_err(invalid)?;
+            if let Part::Literal(ref l) = part {
+                if parser::validate_segment(l).is_err() {
+                    return Err(invalid("path segment is not valid"));
+                }
+            }
+            path_parts.push(part);
+        }
+
+        Ok(SpiffeIdPattern {
+            pattern: pattern.to_string(),
+            trust_domain,
+            path: path_parts,
+            capture_names,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The names of the `{name}` captures, in the order they appear in the pattern.
+    pub fn capture_names(&self) -> &[String] {
+        &self.capture_names
+    }
+
+    pub fn is_match(&self, id: &URI) -> bool {
+        self.captures(id).is_some()
+    }
+
+    /// Matches `id` against the pattern, returning the captured values on success.
+    pub fn captures<'a>(&self, id: &'a URI) -> Option<Captures<'a>> {
+        self.captures_ref(id.as_id_ref())
+    }
+
+    pub fn captures_ref<'a>(&self, id: SpiffeIdRef<'a>) -> Option<Captures<'a>> {
+        let mut captured = Vec::with_capacity(self.capture_names.len());
+
+        let labels: Vec<&'a str> = id.trust_domain_name().split('.').collect();
+        if labels.len() != self.trust_domain.len() {
+            return None;
+        }
+        for (part, label) in self.trust_domain.iter().zip(labels) {
+            if !SpiffeIdPattern::match_part(part, label, &mut captured) {
+                return None;
+            }
+        }
+
+        let segments: Vec<&'a str> = id.segments().collect();
+        if !SpiffeIdPattern::match_path(&self.path, &segments, &mut captured) {
+            return None;
+        }
+
+        let values = captured
+            .into_iter()
+            .map(|(index, value)| (self.capture_names[index].clone(), value))
+            .collect();
+
+        Some(Captures { values })
+    }
+
+    fn parse_part(
+        part: &str,
+        capture_names: &mut Vec<String>,
+    ) -> std::result::Result<Part, &'static str> {
+        match part {
+            "*" => Ok(Part::Wildcard),
+            "**" => Ok(Part::Recursive),
+            _ if part.starts_with('{') && part.ends_with('}') && part.len() > 2 => {
+                let name = &part[1..part.len() - 1];
+                if !name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+                    return Err("capture names may only contain letters, digits and underscores");
+                }
+                if capture_names.iter().any(|n| n == name) {
+                    return Err("capture names must be unique");
+                }
+                capture_names.push(name.to_string());
+                Ok(Part::Capture(capture_names.len() - 1))
+            }
+            _ if part.contains(&['*', '{', '}'][..]) => {
+                Err("wildcards and captures must span a whole label or segment")
+            }
+            _ => Ok(Part::Literal(part.to_string())),
+        }
+    }
+
+    fn match_part<'a>(part: &Part, value: &'a str, captured: &mut Vec<(usize, &'a str)>) -> bool {
+        match part {
+            Part::Literal(literal) => literal == value,
+            Part::Wildcard => true,
+            Part::Capture(index) => {
+                captured.push((*index, value));
+                true
+            }
+            Part::Recursive => false,
+        }
+    }
+
+    fn match_path<'a>(
+        parts: &[Part],
+        segments: &[&'a str],
+        captured: &mut Vec<(usize, &'a str)>,
+    ) -> bool {
+        // Whether the parts from one index on are known not to match the segments from another,
+        // so patterns with several ** don't retry the same split over and over
+        let mut failed = vec![false; (parts.len() + 1) * (segments.len() + 1)];
+        SpiffeIdPattern::match_path_from(parts, segments, 0, 0, captured, &mut failed)
+    }
+
+    fn match_path_from<'a>(
+        parts: &[Part],
+        segments: &[&'a str],
+        part: usize,
+        segment: usize,
+        captured: &mut Vec<(usize, &'a str)>,
+        failed: &mut Vec<bool>,
+    ) -> bool {
+        let state = part * (segments.len() + 1) + segment;
+        if failed[state] {
+            return false;
+        }
+
+        let checkpoint = captured.len();
+        let matched = match parts.get(part) {
+            None => segment == segments.len(),
+            // Try every possible number of consumed segments, dropping captures from failed
+            // attempts before the next one
+            Some(Part::Recursive) => (segment..=segments.len()).any(|next| {
+                captured.truncate(checkpoint);
+                SpiffeIdPattern::match_path_from(parts, segments, part + 1, next, captured, failed)
+            }),
+            Some(p) => {
+                segment < segments.len()
+                    && SpiffeIdPattern::match_part(p, segments[segment], captured)
+                    && SpiffeIdPattern::match_path_from(
+                        parts,
+                        segments,
+                        part + 1,
+                        segment + 1,
+                        captured,
+                        failed,
+                    )
+            }
+        };
+
+        if !matched {
+            captured.truncate(checkpoint);
+            failed[state] = true;
+        }
+        matched
+    }
+}
+
+impl fmt::Display for SpiffeIdPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl FromStr for SpiffeIdPattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<SpiffeIdPattern> {
+        SpiffeIdPattern::new(pattern)
+    }
+}
@@ -0,0 +1,53 @@
+//! Serde support for the SPIFFE ID types. Each type is represented by its string form and is fully
+//! validated on deserialization, so invalid IDs in configuration files are rejected when loaded.
+
+use crate::uri::{SpiffeIdPattern, TrustDomain, URI};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::str::FromStr;
+
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    T::from_str(&s).map_err(de::Error::custom)
+}
+
+impl Serialize for URI {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for URI {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<URI, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Serialize for TrustDomain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for TrustDomain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TrustDomain, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Serialize for SpiffeIdPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SpiffeIdPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SpiffeIdPattern, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
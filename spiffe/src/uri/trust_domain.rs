@@ -0,0 +1,115 @@
+use crate::uri::parser::SPIFFE_SCHEME_PREFIX;
+use crate::uri::{Error, ErrorKind, Result};
+use std::fmt;
+use std::str::FromStr;
+
+const MAX_TRUST_DOMAIN_LENGTH: usize = 255;
+
+/// The name of a SPIFFE trust domain, e.g. `example.org`.
+///
+/// Trust domain names are limited to lowercase letters, digits, dots, dashes and underscores, and
+/// may not exceed 255 bytes. Two trust domains are equal only if their names are byte-for-byte
+/// equal, which the character set restriction makes unambiguous.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct TrustDomain {
+    name: String,
+}
+
+impl TrustDomain {
+    /// Parses a trust domain from either its bare name (`example.org`) or its SPIFFE ID form
+    /// (`spiffe://example.org`). SPIFFE IDs with a path are rejected.
+    pub fn new(id_or_name: &str) -> Result<TrustDomain> {
+        let name = if id_or_name.contains(":/") {
+            if !id_or_name.starts_with(SPIFFE_SCHEME_PREFIX) {
+                return Err(ErrorKind::WrongScheme(id_or_name.to_string()).into());
+            }
+            let name = &id_or_name[SPIFFE_SCHEME_PREFIX.len()..];
+            if name.contains('/') {
+                return Err(ErrorKind::TrustDomainHasPath(id_or_name.to_string()).into());
+            }
+            name
+        } else {
+            id_or_name
+        };
+
+        TrustDomain::validate_name(name)?;
+
+        Ok(TrustDomain {
+            name: name.to_string(),
+        })
+    }
+
+    /// Builds a trust domain from a name the SPIFFE ID parser has already validated.
+    pub(crate) fn from_validated_name(name: &str) -> TrustDomain {
+        TrustDomain {
+            name: name.to_string(),
+        }
+    }
+
+    /// The trust domain name, e.g. `example.org`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The SPIFFE ID of the trust domain, e.g. `spiffe://example.org`.
+    pub fn id_string(&self) -> String {
+        format!("{}{}", SPIFFE_SCHEME_PREFIX, self.name)
+    }
+
+    pub(crate) fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty() {
+            return Err(ErrorKind::MissingTrustDomain.into());
+        }
+
+        if name.len() > MAX_TRUST_DOMAIN_LENGTH {
+            return Err(ErrorKind::TrustDomainTooLong(name.len()).into());
+        }
+
+        for c in name.bytes() {
+            if c.is_ascii_uppercase() {
+                return Err(ErrorKind::UppercaseTrustDomain(name.to_string()).into());
+            }
+            if !TrustDomain::is_valid_char(c) {
+                return Err(ErrorKind::BadTrustDomainChar(name.to_string()).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn is_valid_char(c: u8) -> bool {
+        matches!(c, b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_')
+    }
+}
+
+impl fmt::Display for TrustDomain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl FromStr for TrustDomain {
+    type Err = Error;
+
+    fn from_str(id_or_name: &str) -> Result<TrustDomain> {
+        TrustDomain::new(id_or_name)
+    }
+}
+
+impl AsRef<str> for TrustDomain {
+    fn as_ref(&self) -> &str {
+        &self.name
+    }
+}
+
+impl PartialEq<str> for TrustDomain {
+    fn eq(&self, other: &str) -> bool {
+        self.name == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for TrustDomain {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.name == *other
+    }
+}
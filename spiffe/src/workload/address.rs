@@ -0,0 +1,103 @@
+use crate::workload::{Error, ErrorKind, Result};
+use percent_encoding::percent_decode_str;
+use std::env;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use url::{Host, Url};
+
+/// The environment variable holding the address of the Workload API.
+pub const ENDPOINT_SOCKET_ENV: &str = "SPIFFE_ENDPOINT_SOCKET";
+
+/// The address of the Workload API, as given by `SPIFFE_ENDPOINT_SOCKET`: either `unix:` with an
+/// absolute path, or `tcp://` with an IP address and a port.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EndpointAddress {
+    Unix(PathBuf),
+    Tcp(SocketAddr),
+}
+
+impl EndpointAddress {
+    /// The address in `SPIFFE_ENDPOINT_SOCKET`.
+    pub fn from_env() -> Result<EndpointAddress> {
+        match env::var(ENDPOINT_SOCKET_ENV) {
+            Ok(addr) if !addr.is_empty() => addr.parse(),
+            _ => Err(ErrorKind::EndpointSocketNotSet.into()),
+        }
+    }
+
+    /// The address as a gRPC target.
+    pub fn target(&self) -> String {
+        match self {
+            EndpointAddress::Unix(path) => format!("unix:{}", path.display()),
+            EndpointAddress::Tcp(addr) => addr.to_string(),
+        }
+    }
+}
+
+impl FromStr for EndpointAddress {
+    type Err = Error;
+
+    fn from_str(addr: &str) -> Result<EndpointAddress> {
+        let url =
+            Url::parse(addr).map_err(|_| ErrorKind::InvalidEndpointAddress(addr.to_string()))?;
+
+        if url.scheme() != "unix" && url.scheme() != "tcp" {
+            return Err(ErrorKind::UnsupportedEndpointScheme(addr.to_string()).into());
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err(ErrorKind::EndpointAddressHasUserInfo(addr.to_string()).into());
+        }
+        if url.query().is_some() {
+            return Err(ErrorKind::EndpointAddressHasQuery(addr.to_string()).into());
+        }
+        if url.fragment().is_some() {
+            return Err(ErrorKind::EndpointAddressHasFragment(addr.to_string()).into());
+        }
+
+        if url.scheme() == "unix" {
+            if url.host().is_some() {
+                return Err(ErrorKind::UnixEndpointHasAuthority(addr.to_string()).into());
+            }
+            // Relative paths make the URL opaque, e.g. unix:agent.sock
+            if url.cannot_be_a_base() || !url.path().starts_with('/') {
+                return Err(ErrorKind::UnixEndpointPathNotAbsolute(addr.to_string()).into());
+            }
+            // Paths are percent-encoded in URLs, but not on the file system
+            let path = percent_decode_str(url.path())
+                .decode_utf8()
+                .ok()
+                .filter(|path| !path.contains('\0'))
+                .ok_or_else(|| ErrorKind::InvalidEndpointAddress(addr.to_string()))?;
+            return Ok(EndpointAddress::Unix(PathBuf::from(path.as_ref())));
+        }
+
+        let ip = match url.host() {
+            Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+            // Hosts of URLs with schemes other than http and the like are not parsed
+            Some(Host::Domain(host)) => host
+                .parse()
+                .map_err(|_| ErrorKind::TcpEndpointHostNotIP(addr.to_string()))?,
+            None => return Err(ErrorKind::TcpEndpointHostNotIP(addr.to_string()).into()),
+        };
+        let port = url
+            .port()
+            .ok_or_else(|| ErrorKind::TcpEndpointMissingPort(addr.to_string()))?;
+        if !url.path().is_empty() {
+            return Err(ErrorKind::TcpEndpointHasPath(addr.to_string()).into());
+        }
+
+        Ok(EndpointAddress::Tcp(SocketAddr::new(ip, port)))
+    }
+}
+
+impl fmt::Display for EndpointAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndpointAddress::Unix(path) => write!(f, "unix://{}", path.display()),
+            EndpointAddress::Tcp(addr) => write!(f, "tcp://{}", addr),
+        }
+    }
+}
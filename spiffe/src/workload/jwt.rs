@@ -1,22 +1,37 @@
+use crate::bundle::jwt::JwtBundleSet;
+use crate::bundle::spiffe::SpiffeBundle;
+use crate::svid;
 use crate::svid::jwt::Jwt;
 use crate::svid::SVID;
-use crate::uri::URI;
-use crate::workload::workload_api::{JWTSVIDRequest, ValidateJWTSVIDRequest};
+use crate::uri::{TrustDomain, URI};
+use crate::workload::address::EndpointAddress;
+use crate::workload::workload_api::{
+    JWTBundlesRequest, JWTBundlesResponse, JWTSVIDRequest, JWTSVIDResponse, ValidateJWTSVIDRequest,
+    ValidateJWTSVIDResponse,
+};
 use crate::workload::workload_api_grpc::SpiffeWorkloadApiClient;
 use crate::workload::INITIAL_CONNECTION_TIMEOUT;
 use crate::workload::MAX_CLIENT_BACKOFF;
-use crate::workload::{ErrorKind, Result, ResultExt};
-use grpcio::{ChannelBuilder, EnvBuilder};
+use crate::workload::{Error, ErrorKind, Result, ResultExt};
+use futures::executor::block_on;
+use futures::stream;
+use futures::StreamExt;
+use grpcio::{CallOption, ChannelBuilder, EnvBuilder};
 use log::error;
+use protobuf::well_known_types::{ListValue, NullValue, Value};
+use protobuf::RepeatedField;
+use serde_json::Map;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+#[derive(Clone)]
 pub struct JWTClient {
     client: SpiffeWorkloadApiClient,
     timeout: Duration,
 }
 
+#[derive(Debug)]
 pub struct ValidateResponse {
     spiffe_id: URI,
     claims: Option<protobuf::well_known_types::Struct>,
@@ -34,6 +49,73 @@ impl ValidateResponse {
 
 pub use protobuf::well_known_types::Struct;
 
+pub type JwtBundlesResponse = JWTBundlesResponse;
+
+pub type JwtBundlesStream = ::grpcio::ClientSStreamReceiver<JWTBundlesResponse>;
+
+/// Parses the JWKS of each trust domain in a `FetchJWTBundles` response. Keys of the map may be
+/// trust domain names or SPIFFE IDs.
+pub fn parse_bundles(response: JwtBundlesResponse) -> Result<JwtBundleSet> {
+    let mut bundles = JwtBundleSet::new();
+    for (id, jwks) in response.bundles.into_iter() {
+        let bundle = SpiffeBundle::parse(TrustDomain::new(&id)?, &jwks)?;
+        bundles.insert(bundle.into_bundles().1);
+    }
+    Ok(bundles)
+}
+
+/// Validates a JWT-SVID locally against the bundle of its trust domain in `bundles`, like
+/// `JWTClient::validate` does through the Workload API, but without a round trip to the agent.
+/// See `Jwt::verify` for what is checked.
+pub fn validate_offline(
+    audience: &str,
+    svid: &Jwt,
+    bundles: &JwtBundleSet,
+) -> Result<ValidateResponse> {
+    let subject = svid.claims().subject();
+    let spiffe_id = bundles
+        .get_for_id(subject)
+        .ok_or_else(|| {
+            svid::jwt::ErrorKind::BundleNotFound(subject.trust_domain().to_string()).into()
+        })
+        .and_then(|bundle| svid.verify(bundle, audience))
+        .chain_err(|| ErrorKind::ValidateFailure)?;
+
+    Ok(ValidateResponse {
+        spiffe_id,
+        claims: Some(to_struct(svid.claims().to_map())),
+    })
+}
+
+fn to_struct(map: Map<String, serde_json::Value>) -> Struct {
+    let mut fields = Struct::new();
+    for (name, value) in map {
+        fields.mut_fields().insert(name, to_value(value));
+    }
+    fields
+}
+
+fn to_value(value: serde_json::Value) -> Value {
+    let mut converted = Value::new();
+    match value {
+        serde_json::Value::Null => converted.set_null_value(NullValue::NULL_VALUE),
+        serde_json::Value::Bool(value) => converted.set_bool_value(value),
+        serde_json::Value::Number(value) => {
+            converted.set_number_value(value.as_f64().unwrap_or_default())
+        }
+        serde_json::Value::String(value) => converted.set_string_value(value),
+        serde_json::Value::Array(values) => {
+            let mut list = ListValue::new();
+            list.set_values(RepeatedField::from_vec(
+                values.into_iter().map(to_value).collect(),
+            ));
+            converted.set_list_value(list);
+        }
+        serde_json::Value::Object(map) => converted.set_struct_value(to_struct(map)),
+    }
+    converted
+}
+
 impl JWTClient {
     pub fn new(addr: &str, backoff: Option<Duration>, timeout: Option<Duration>) -> JWTClient {
         let backoff = backoff.unwrap_or(*MAX_CLIENT_BACKOFF);
@@ -48,58 +130,213 @@ impl JWTClient {
         }
     }
 
-    pub fn validate(&self, audience: String, svid: Jwt) -> Result<ValidateResponse> {
-        let mut metadata = ::grpcio::MetadataBuilder::new();
-        metadata
-            .add_str("workload.spiffe.io", "true")
-            .chain_err(|| ErrorKind::ClientConfigFailure)?;
-
-        let options = ::grpcio::CallOption::default()
-            .timeout(self.timeout)
-            .headers(metadata.build());
+    /// A client for the Workload API at `SPIFFE_ENDPOINT_SOCKET`.
+    pub fn from_env(backoff: Option<Duration>, timeout: Option<Duration>) -> Result<JWTClient> {
+        Ok(JWTClient::with_address(
+            &EndpointAddress::from_env()?,
+            backoff,
+            timeout,
+        ))
+    }
 
-        let mut req = ValidateJWTSVIDRequest::new();
-        req.set_audience(audience);
-        req.set_svid((&svid.svid()).to_string());
+    /// A client for the Workload API at `addr`, which unlike the address given to `new` has been
+    /// validated.
+    pub fn with_address(
+        addr: &EndpointAddress,
+        backoff: Option<Duration>,
+        timeout: Option<Duration>,
+    ) -> JWTClient {
+        JWTClient::new(&addr.target(), backoff, timeout)
+    }
 
+    pub fn validate(&self, audience: String, svid: Jwt) -> Result<ValidateResponse> {
         let res = self
             .client
-            .validate_jwtsvid_opt(&req, options)
+            .validate_jwtsvid_opt(&validate_request(audience, &svid), self.options(None)?)
             .map_err(|e| {
                 error!("Error during validation: {}.", e);
                 ErrorKind::ValidateFailure
             })?;
 
-        Ok(ValidateResponse {
-            spiffe_id: URI::from_str(&res.spiffe_id).chain_err(|| ErrorKind::ValidateFailure)?,
-            claims: res.claims.into_option(),
-        })
+        validate_response(res)
+    }
+
+    /// Like `validate`, without blocking the calling thread.
+    pub async fn validate_async(&self, audience: String, svid: Jwt) -> Result<ValidateResponse> {
+        let failure = |e: ::grpcio::Error| {
+            error!("Error during validation: {}.", e);
+            ErrorKind::ValidateFailure
+        };
+        let res = self
+            .client
+            .validate_jwtsvid_async_opt(&validate_request(audience, &svid), self.options(None)?)
+            .map_err(failure)?
+            .await
+            .map_err(failure)?;
+
+        validate_response(res)
     }
 
     /// Fetch the first JWT-SVID of the workload
     pub fn fetch(&self, audience: String) -> Result<SVID<Jwt>> {
-        let mut metadata = ::grpcio::MetadataBuilder::new();
-        metadata
-            .add_str("workload.spiffe.io", "true")
-            .chain_err(|| ErrorKind::ClientConfigFailure)?;
+        first(self.fetch_all(vec![audience], None)?)
+    }
+
+    /// Like `fetch`, without blocking the calling thread.
+    pub async fn fetch_async(&self, audience: String) -> Result<SVID<Jwt>> {
+        first(self.fetch_all_async(vec![audience], None).await?)
+    }
 
-        let options = ::grpcio::CallOption::default()
-            .timeout(self.timeout)
-            .headers(metadata.build());
+    /// Fetch every JWT-SVID of the workload for `audience`, in the order the agent sent them, or
+    /// only the one of `spiffe_id`. Each token must be for the SPIFFE ID reported alongside it.
+    pub fn fetch_all(
+        &self,
+        audience: Vec<String>,
+        spiffe_id: Option<&URI>,
+    ) -> Result<Vec<SVID<Jwt>>> {
+        let res = self
+            .client
+            .fetch_jwtsvid_opt(&svid_request(audience, spiffe_id), self.options(None)?)
+            .chain_err(|| ErrorKind::FetchFailure)?;
 
-        let mut req = JWTSVIDRequest::new();
-        let mut audience_field = protobuf::RepeatedField::new();
-        audience_field.push(audience);
-        req.set_audience(audience_field);
+        svids(res, spiffe_id)
+    }
 
-        let mut res = self
+    /// Like `fetch_all`, without blocking the calling thread.
+    pub async fn fetch_all_async(
+        &self,
+        audience: Vec<String>,
+        spiffe_id: Option<&URI>,
+    ) -> Result<Vec<SVID<Jwt>>> {
+        let res = self
             .client
-            .fetch_jwtsvid_opt(&req, options)
+            .fetch_jwtsvid_async_opt(&svid_request(audience, spiffe_id), self.options(None)?)
+            .chain_err(|| ErrorKind::FetchFailure)?
+            .await
             .chain_err(|| ErrorKind::FetchFailure)?;
 
-        // Only take the first one.
-        let svid = res.svids.pop().chain_err(|| ErrorKind::FetchFailure)?;
+        svids(res, spiffe_id)
+    }
+
+    /// Fetch the JWT bundles of the trust domain and the federated ones
+    pub fn fetch_bundles(&self) -> Result<JwtBundleSet> {
+        block_on(self.fetch_bundles_async())
+    }
+
+    /// Like `fetch_bundles`, without blocking the calling thread.
+    pub async fn fetch_bundles_async(&self) -> Result<JwtBundleSet> {
+        let mut rx = self.stream_bundles_async(None)?;
+
+        match rx.next().await {
+            Some(bundles) => bundles,
+            None => Err(ErrorKind::FetchFailure.into()),
+        }
+    }
+
+    /// Stream the JWT bundles, a new response being sent whenever they change. The stream ends
+    /// after `timeout`, or the timeout of the client if `None`.
+    pub fn stream_bundles(&self, timeout: Option<Duration>) -> Result<JwtBundlesStream> {
+        self.open_bundles_stream(Some(timeout.unwrap_or(self.timeout)))
+    }
+
+    /// Like `stream_bundles`, with the responses parsed.
+    pub fn stream_bundles_async(&self, timeout: Option<Duration>) -> Result<JwtBundleSetStream> {
+        Ok(self.stream_bundles(timeout)?.map(bundle_set))
+    }
+
+    /// Opens a bundle stream that only ends with the connection if there is no `deadline`.
+    pub(crate) fn open_bundles_stream(
+        &self,
+        deadline: Option<Duration>,
+    ) -> Result<JwtBundlesStream> {
+        let rx = self
+            .client
+            .fetch_jwt_bundles_opt(&JWTBundlesRequest::new(), call_options(deadline)?)
+            .chain_err(|| ErrorKind::ClientConfigFailure)?;
+
+        Ok(rx)
+    }
+
+    fn options(&self, timeout: Option<Duration>) -> Result<CallOption> {
+        call_options(Some(timeout.unwrap_or(self.timeout)))
+    }
+}
+
+fn call_options(deadline: Option<Duration>) -> Result<CallOption> {
+    let mut metadata = ::grpcio::MetadataBuilder::new();
+    metadata
+        .add_str("workload.spiffe.io", "true")
+        .chain_err(|| ErrorKind::ClientConfigFailure)?;
+
+    let options = CallOption::default().headers(metadata.build());
+    Ok(match deadline {
+        Some(deadline) => options.timeout(deadline),
+        None => options,
+    })
+}
+
+/// Parsed JWT bundles, see `JWTClient::stream_bundles_async`.
+pub type JwtBundleSetStream =
+    stream::Map<JwtBundlesStream, fn(::grpcio::Result<JWTBundlesResponse>) -> Result<JwtBundleSet>>;
+
+pub(crate) fn bundle_set(response: ::grpcio::Result<JWTBundlesResponse>) -> Result<JwtBundleSet> {
+    match response {
+        Ok(response) => parse_bundles(response).chain_err(|| ErrorKind::FetchFailure),
+        Err(e) => Err(Error::with_chain(e, ErrorKind::FetchFailure)),
+    }
+}
+
+fn validate_request(audience: String, svid: &Jwt) -> ValidateJWTSVIDRequest {
+    let mut req = ValidateJWTSVIDRequest::new();
+    req.set_audience(audience);
+    req.set_svid(svid.svid().to_string());
+    req
+}
+
+fn validate_response(res: ValidateJWTSVIDResponse) -> Result<ValidateResponse> {
+    Ok(ValidateResponse {
+        spiffe_id: URI::from_str(&res.spiffe_id).chain_err(|| ErrorKind::ValidateFailure)?,
+        claims: res.claims.into_option(),
+    })
+}
+
+fn svid_request(audience: Vec<String>, spiffe_id: Option<&URI>) -> JWTSVIDRequest {
+    let mut req = JWTSVIDRequest::new();
+    req.set_audience(RepeatedField::from_vec(audience));
+    if let Some(spiffe_id) = spiffe_id {
+        req.set_spiffe_id(spiffe_id.as_str().to_string());
+    }
+    req
+}
+
+fn svids(res: JWTSVIDResponse, spiffe_id: Option<&URI>) -> Result<Vec<SVID<Jwt>>> {
+    let mut svids = Vec::with_capacity(res.svids.len());
+    for svid in res.svids.into_iter() {
+        let jwt = SVID::<Jwt>::new(svid.svid).chain_err(|| ErrorKind::FetchFailure)?;
+
+        // The SPIFFE ID is taken from the token, the one reported alongside must agree
+        if jwt.uri().as_str() != svid.spiffe_id {
+            error!(
+                "JWT-SVID subject {} does not match SPIFFE ID {}.",
+                jwt.uri(),
+                svid.spiffe_id
+            );
+            return Err(ErrorKind::FetchFailure.into());
+        }
+        if spiffe_id.map_or(false, |id| id != jwt.uri()) {
+            error!("Received JWT-SVID for unrequested SPIFFE ID {}.", jwt.uri());
+            return Err(ErrorKind::FetchFailure.into());
+        }
+
+        svids.push(jwt);
+    }
+
+    Ok(svids)
+}
 
-        SVID::<Jwt>::new(svid.svid, &svid.spiffe_id).chain_err(|| ErrorKind::FetchFailure)
+fn first(mut svids: Vec<SVID<Jwt>>) -> Result<SVID<Jwt>> {
+    if svids.is_empty() {
+        return Err(ErrorKind::FetchFailure.into());
     }
+    Ok(svids.swap_remove(0))
 }
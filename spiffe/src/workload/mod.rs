@@ -1,8 +1,11 @@
+pub mod address;
 pub mod jwt;
+pub mod source;
 mod workload_api;
 mod workload_api_grpc;
 pub mod x509;
 
+use crate::bundle;
 use crate::svid;
 use crate::uri;
 use error_chain::error_chain;
@@ -28,15 +31,72 @@ error_chain! {
             description("An error during the the validation of api payload")
             display("Unable to validate api payload")
         }
+
+        EndpointSocketNotSet {
+            description("An error during the lookup of the Workload API address")
+            display("Workload API address is not set: SPIFFE_ENDPOINT_SOCKET is missing or empty")
+        }
+
+        InvalidEndpointAddress(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: {} is not a URI", addr)
+        }
+
+        UnsupportedEndpointScheme(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: scheme is not unix or tcp in {}", addr)
+        }
+
+        EndpointAddressHasUserInfo(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: {} has user info", addr)
+        }
+
+        EndpointAddressHasQuery(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: {} has a query", addr)
+        }
+
+        EndpointAddressHasFragment(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: {} has a fragment", addr)
+        }
+
+        UnixEndpointHasAuthority(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: unix address {} has an authority", addr)
+        }
+
+        UnixEndpointPathNotAbsolute(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: unix address {} has no absolute path", addr)
+        }
+
+        TcpEndpointHostNotIP(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: tcp address {} has no IP address as host", addr)
+        }
+
+        TcpEndpointMissingPort(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: tcp address {} has no port", addr)
+        }
+
+        TcpEndpointHasPath(addr: String) {
+            description("An error during the parsing of a Workload API address")
+            display("Not a valid Workload API address: tcp address {} has a path", addr)
+        }
     }
 
     links {
         Uri(uri::Error, uri::ErrorKind);
+        Bundle(bundle::Error, bundle::ErrorKind);
         X509SVID(svid::x509::Error, svid::x509::ErrorKind);
         JWTSVID(svid::jwt::Error, svid::jwt::ErrorKind);
     }
 
     foreign_links {
         GRPCIO(grpcio::Error);
+        Io(::std::io::Error);
     }
 }
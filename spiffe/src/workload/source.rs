@@ -0,0 +1,410 @@
+use crate::bundle::jwt::{JwtBundle, JwtBundleSet};
+use crate::bundle::x509::X509Bundle;
+use crate::svid::jwt::Jwt;
+use crate::svid::x509::X509;
+use crate::svid::SVID;
+use crate::uri::{TrustDomain, URI};
+use crate::workload::jwt::{self, JWTClient, ValidateResponse};
+use crate::workload::x509::{self, X509Client, X509Payload};
+use crate::workload::INITIAL_CONNECTION_TIMEOUT;
+use crate::workload::{ErrorKind, Result};
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use futures::{Stream, StreamExt};
+use log::warn;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+use tokio::runtime::{Builder, Runtime};
+
+/// Reconnections start this far apart and back off up to `MAX_RETRY_INTERVAL`.
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The X.509-SVIDs and bundles of the workload, kept up to date from the Workload API on a
+/// background thread. Dropping the source stops it.
+///
+/// When the stream fails the source reconnects, serving the last payload it received meanwhile.
+pub struct X509Source {
+    updater: Updater<X509Payload>,
+}
+
+impl X509Source {
+    /// Streams payloads from `client` and waits up to `timeout` (15 seconds if `None`) for the
+    /// first one.
+    pub fn new(client: X509Client, timeout: Option<Duration>) -> Result<X509Source> {
+        let updater = Updater::start(
+            move || Ok(client.open_stream(None)?.map(x509::payload)),
+            timeout.unwrap_or(*INITIAL_CONNECTION_TIMEOUT),
+        )?;
+        Ok(X509Source { updater })
+    }
+
+    /// The latest payload. It is only `None` before the first one was received, which `new`
+    /// waits for.
+    pub fn payload(&self) -> Option<Arc<X509Payload>> {
+        self.updater.latest()
+    }
+
+    /// The first X.509-SVID of the latest payload, which the Workload API designates as the
+    /// default one.
+    pub fn default_svid(&self) -> Option<X509SVIDRef> {
+        self.svids().into_iter().next()
+    }
+
+    pub fn svids(&self) -> Vec<X509SVIDRef> {
+        match self.payload() {
+            Some(payload) => (0..payload.svids().len())
+                .map(|index| X509SVIDRef {
+                    payload: Arc::clone(&payload),
+                    index,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// The bundle of `trust_domain` in the latest payload, whether it is the workload's own
+    /// trust domain or a federated one.
+    pub fn bundle_for(&self, trust_domain: &TrustDomain) -> Option<X509Bundle> {
+        self.payload()?.bundles().get(trust_domain).cloned()
+    }
+
+    /// Receives every payload from now on, starting with the latest one.
+    pub fn subscribe(&self) -> Receiver<Arc<X509Payload>> {
+        self.updater.subscribe()
+    }
+
+    /// Stops the source and waits for the background thread to exit, like dropping it does.
+    pub fn close(self) {}
+}
+
+impl fmt::Debug for X509Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("X509Source")
+            .field("payload", &self.payload())
+            .finish()
+    }
+}
+
+/// An X.509-SVID of a payload of an `X509Source`. It keeps the payload alive, so it stays usable
+/// after the source has moved on.
+#[derive(Clone, Debug)]
+pub struct X509SVIDRef {
+    payload: Arc<X509Payload>,
+    index: usize,
+}
+
+impl X509SVIDRef {
+    /// The payload the SVID came with, which holds the bundles it is verified against.
+    pub fn payload(&self) -> &Arc<X509Payload> {
+        &self.payload
+    }
+}
+
+impl Deref for X509SVIDRef {
+    type Target = SVID<X509>;
+
+    fn deref(&self) -> &SVID<X509> {
+        &self.payload.svids()[self.index]
+    }
+}
+
+/// The JWT bundles of the workload, kept up to date from the Workload API on a background thread,
+/// and a cache of its JWT-SVIDs. Dropping the source stops it.
+///
+/// Cached JWT-SVIDs are refetched once half their lifetime has passed. Refreshes are lazy: nothing
+/// is fetched in the background, so the first caller after that point waits for the Workload API.
+/// Callers asking for the same JWT-SVID meanwhile wait for that fetch instead of making their own.
+/// Expired JWT-SVIDs are evicted from the cache.
+pub struct JwtSource {
+    client: JWTClient,
+    updater: Updater<JwtBundleSet>,
+    svids: Mutex<SVIDCache>,
+}
+
+/// Audiences, sorted and deduplicated, and the requested SPIFFE ID.
+type SVIDKey = (Vec<String>, Option<URI>);
+
+/// Each entry is locked while it is refreshed.
+type SVIDCache = HashMap<SVIDKey, Arc<Mutex<Option<CachedSVID>>>>;
+
+struct CachedSVID {
+    svid: Arc<SVID<Jwt>>,
+    refresh_at: SystemTime,
+}
+
+impl JwtSource {
+    /// Streams bundles with `client` and waits up to `timeout` (15 seconds if `None`) for the
+    /// first ones.
+    pub fn new(client: JWTClient, timeout: Option<Duration>) -> Result<JwtSource> {
+        let stream_client = client.clone();
+        let updater = Updater::start(
+            move || {
+                Ok(stream_client
+                    .open_bundles_stream(None)?
+                    .map(jwt::bundle_set))
+            },
+            timeout.unwrap_or(*INITIAL_CONNECTION_TIMEOUT),
+        )?;
+        Ok(JwtSource {
+            client,
+            updater,
+            svids: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The latest bundles. They are only `None` before the first ones were received, which `new`
+    /// waits for.
+    pub fn bundles(&self) -> Option<Arc<JwtBundleSet>> {
+        self.updater.latest()
+    }
+
+    /// The bundle of `trust_domain` in the latest bundles, whether it is the workload's own trust
+    /// domain or a federated one.
+    pub fn bundle_for(&self, trust_domain: &TrustDomain) -> Option<JwtBundle> {
+        self.bundles()?.get(trust_domain).cloned()
+    }
+
+    /// Receives all bundles from now on, starting with the latest ones.
+    pub fn subscribe(&self) -> Receiver<Arc<JwtBundleSet>> {
+        self.updater.subscribe()
+    }
+
+    /// Validates a JWT-SVID against the latest bundles, see `jwt::validate_offline`.
+    pub fn validate(&self, audience: &str, svid: &Jwt) -> Result<ValidateResponse> {
+        let bundles = self.bundles().unwrap_or_default();
+        jwt::validate_offline(audience, svid, &bundles)
+    }
+
+    /// A JWT-SVID for `audience`, of `spiffe_id` or else the default one of the workload, from
+    /// the cache unless it is due for a refresh.
+    ///
+    /// If the refresh fails, the cached JWT-SVID is returned as long as it has not expired.
+    pub fn fetch(&self, audience: &[String], spiffe_id: Option<&URI>) -> Result<Arc<SVID<Jwt>>> {
+        let mut audience = audience.to_vec();
+        audience.sort();
+        audience.dedup();
+
+        let now = SystemTime::now();
+        let entry = {
+            let mut svids = lock_svids(&self.svids);
+            evict_expired(&mut svids, now);
+            svids
+                .entry((audience.clone(), spiffe_id.cloned()))
+                .or_default()
+                .clone()
+        };
+
+        // Held across the fetch, so concurrent callers for the same key wait for its result
+        let mut cached = entry.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cached.as_ref().filter(|cached| now < cached.refresh_at) {
+            return Ok(Arc::clone(&cached.svid));
+        }
+
+        match self.fetch_svid(audience, spiffe_id) {
+            Ok(svid) => {
+                let svid = Arc::new(svid);
+                *cached = Some(CachedSVID {
+                    svid: Arc::clone(&svid),
+                    refresh_at: refresh_at(&svid, now),
+                });
+                Ok(svid)
+            }
+            Err(e) => match cached.as_ref().filter(|cached| now < cached.svid.expiry()) {
+                Some(cached) => {
+                    warn!("Unable to refresh JWT-SVID {}: {}.", cached.svid.uri(), e);
+                    Ok(Arc::clone(&cached.svid))
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    fn fetch_svid(&self, audience: Vec<String>, spiffe_id: Option<&URI>) -> Result<SVID<Jwt>> {
+        let mut svids = self.client.fetch_all(audience, spiffe_id)?;
+        if svids.is_empty() {
+            return Err(ErrorKind::FetchFailure.into());
+        }
+        Ok(svids.swap_remove(0))
+    }
+
+    /// Stops the source and waits for the background thread to exit, like dropping it does.
+    pub fn close(self) {}
+}
+
+impl fmt::Debug for JwtSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JwtSource")
+            .field("bundles", &self.bundles())
+            .finish()
+    }
+}
+
+/// Half-way through the lifetime of the JWT-SVID, counted from when it was fetched if it has no
+/// `iat`.
+fn refresh_at(svid: &SVID<Jwt>, fetched_at: SystemTime) -> SystemTime {
+    let issued_at = svid.claims().issued_at().unwrap_or(fetched_at);
+    let lifetime = svid.expiry().duration_since(issued_at).unwrap_or_default();
+    issued_at + lifetime / 2
+}
+
+/// Removes the entries whose JWT-SVID has expired, or that hold none, unless a caller is
+/// refreshing or waiting for them.
+fn evict_expired(svids: &mut SVIDCache, now: SystemTime) {
+    svids.retain(|_, entry| {
+        // Callers clone the entry before locking it, so an unlocked entry may still be in use
+        if Arc::strong_count(entry) > 1 {
+            return true;
+        }
+        match entry.try_lock() {
+            Ok(cached) => cached
+                .as_ref()
+                .map_or(false, |cached| now < cached.svid.expiry()),
+            Err(_) => true,
+        }
+    });
+}
+
+fn lock_svids(svids: &Mutex<SVIDCache>) -> MutexGuard<'_, SVIDCache> {
+    // Entries are only added or removed whole, so a poisoned lock is still usable
+    svids.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct Updates<T> {
+    latest: Option<Arc<T>>,
+    senders: Vec<Sender<Arc<T>>>,
+}
+
+impl<T> Updates<T> {
+    fn publish(&mut self, update: T) {
+        let update = Arc::new(update);
+        self.senders
+            .retain(|sender| sender.send(Arc::clone(&update)).is_ok());
+        self.latest = Some(update);
+    }
+}
+
+fn lock<T>(updates: &Mutex<Updates<T>>) -> MutexGuard<'_, Updates<T>> {
+    // Publishing cannot leave the updates inconsistent, so a poisoned lock is still usable
+    updates.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Publishes the items of the streams `open` returns on a background thread, reopening them when
+/// they end or fail to open. Dropping the updater stops it.
+struct Updater<T> {
+    updates: Arc<Mutex<Updates<T>>>,
+    stop: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + Sync + 'static> Updater<T> {
+    /// Starts the updater and waits up to `timeout` for the first item.
+    fn start<F, S>(open: F, timeout: Duration) -> Result<Updater<T>>
+    where
+        F: FnMut() -> Result<S> + Send + 'static,
+        S: Stream<Item = Result<T>> + Unpin,
+    {
+        let (sender, first) = mpsc::channel();
+        let updates = Arc::new(Mutex::new(Updates {
+            latest: None,
+            senders: vec![sender],
+        }));
+        let runtime = Builder::new().basic_scheduler().enable_all().build()?;
+        let (stop, stopped) = oneshot::channel();
+
+        let thread_updates = Arc::clone(&updates);
+        let handle = thread::spawn(move || {
+            follow(
+                runtime,
+                open,
+                |update| lock(&thread_updates).publish(update),
+                stopped,
+            )
+        });
+
+        let updater = Updater {
+            updates,
+            stop: Some(stop),
+            handle: Some(handle),
+        };
+
+        // Dropping the updater on failure stops the thread
+        first
+            .recv_timeout(timeout)
+            .map_err(|_| ErrorKind::FetchFailure)?;
+        Ok(updater)
+    }
+
+    fn latest(&self) -> Option<Arc<T>> {
+        lock(&self.updates).latest.clone()
+    }
+
+    fn subscribe(&self) -> Receiver<Arc<T>> {
+        let (sender, receiver) = mpsc::channel();
+        let mut updates = lock(&self.updates);
+        if let Some(latest) = &updates.latest {
+            // The receiver is still in scope, so this cannot fail
+            let _ = sender.send(Arc::clone(latest));
+        }
+        updates.senders.push(sender);
+        receiver
+    }
+}
+
+impl<T> Drop for Updater<T> {
+    fn drop(&mut self) {
+        // Cancelling wakes the thread, which exits without waiting for the stream
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Passes the items of the streams `open` returns to `update` until `stopped` is cancelled,
+/// reopening them with backoff when they end or fail to open.
+pub(crate) fn follow<T, F, S, U>(
+    mut runtime: Runtime,
+    mut open: F,
+    mut update: U,
+    mut stopped: oneshot::Receiver<()>,
+) where
+    F: FnMut() -> Result<S>,
+    S: Stream<Item = Result<T>> + Unpin,
+    U: FnMut(T),
+{
+    runtime.block_on(async {
+        let mut retry_interval = MIN_RETRY_INTERVAL;
+
+        loop {
+            match open() {
+                Ok(mut stream) => loop {
+                    match future::select(stream.next(), &mut stopped).await {
+                        Either::Left((Some(Ok(item)), _)) => {
+                            retry_interval = MIN_RETRY_INTERVAL;
+                            update(item);
+                        }
+                        // The last good update is kept
+                        Either::Left((Some(Err(e)), _)) => {
+                            warn!("Unable to update from the Workload API: {}.", e)
+                        }
+                        Either::Left((None, _)) => break,
+                        Either::Right(_) => return,
+                    }
+                },
+                Err(e) => warn!("Unable to connect to the Workload API: {}.", e),
+            }
+
+            let delay = tokio::time::delay_for(retry_interval);
+            if let Either::Right(_) = future::select(delay, &mut stopped).await {
+                return;
+            }
+            retry_interval = (retry_interval * 2).min(MAX_RETRY_INTERVAL);
+        }
+    })
+}
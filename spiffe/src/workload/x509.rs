@@ -1,13 +1,18 @@
-use crate::svid::{x509::Bundle, x509::X509, SVID};
+use crate::bundle::x509::{X509Bundle, X509BundleSet};
+use crate::svid::{x509::X509Summary, x509::X509, SVID};
+use crate::uri::TrustDomain;
+use crate::workload::address::EndpointAddress;
 use crate::workload::workload_api::{X509SVIDRequest, X509SVIDResponse};
 use crate::workload::workload_api_grpc::SpiffeWorkloadApiClient;
 use crate::workload::INITIAL_CONNECTION_TIMEOUT;
 use crate::workload::MAX_CLIENT_BACKOFF;
 use crate::workload::{Error, ErrorKind, Result, ResultExt};
 use futures::executor::block_on;
+use futures::stream;
 use futures::StreamExt;
 use grpcio::{ChannelBuilder, EnvBuilder};
-use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 use std::vec::Vec;
@@ -17,29 +22,73 @@ type CRL = Vec<u8>;
 #[derive(Debug)]
 pub struct X509Payload {
     svids: Vec<SVID<X509>>,
-    federated_bundles: HashMap<String, Bundle>,
+    bundles: X509BundleSet,
+    federated_bundles: X509BundleSet,
     crl: Vec<CRL>,
 }
 
 pub type X509Response = X509SVIDResponse;
 
+/// A serializable description of an `X509Payload` that carries no key material.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct X509PayloadSummary {
+    svids: Vec<X509Summary>,
+    federated_trust_domains: Vec<TrustDomain>,
+    crl_count: usize,
+}
+
+impl X509PayloadSummary {
+    pub fn svids(&self) -> &[X509Summary] {
+        &self.svids
+    }
+
+    /// The trust domains of the federated bundles, sorted by name.
+    pub fn federated_trust_domains(&self) -> &[TrustDomain] {
+        &self.federated_trust_domains
+    }
+
+    pub fn crl_count(&self) -> usize {
+        self.crl_count
+    }
+}
+
 impl X509Payload {
     pub fn new(response: X509Response) -> Result<X509Payload> {
         let mut svids = Vec::<SVID<X509>>::with_capacity(response.svids.len());
 
-        for x in response.svids.into_iter() {
+        for mut x in response.svids.into_iter() {
+            // Moved rather than copied, so the key buffer is zeroized once parsed
             let svid = SVID::<X509>::from_der(
                 &x.x509_svid,
-                Some(x.bundle.to_vec()),
-                Some(x.x509_svid_key.to_vec()),
+                Some(std::mem::take(&mut x.x509_svid_key)),
+                Some(std::mem::take(&mut x.bundle)),
             )?;
 
             svids.push(svid);
         }
 
+        // Federated bundles are keyed by the SPIFFE ID of the foreign trust domain
+        let mut federated_bundles = X509BundleSet::new();
+        for (id, der) in response.federated_bundles.into_iter() {
+            federated_bundles.insert(X509Bundle::from_der(TrustDomain::new(&id)?, &der)?);
+        }
+
+        // SVIDs of the same trust domain may carry different bundles while authorities rotate
+        let mut bundles = federated_bundles.clone();
+        for svid in svids.iter() {
+            if let Some(der) = svid.bundle() {
+                bundles.merge(X509Bundle::from_der(
+                    svid.uri().trust_domain().clone(),
+                    der,
+                )?);
+            }
+        }
+
         Ok(X509Payload {
             svids,
-            federated_bundles: response.federated_bundles,
+            bundles,
+            federated_bundles,
             crl: response.crl.into_vec(),
         })
     }
@@ -48,13 +97,31 @@ impl X509Payload {
         &self.svids
     }
 
-    pub fn federated_bundles(&self) -> &HashMap<String, Bundle> {
+    /// The bundles of the SVIDs' own trust domains together with the federated bundles.
+    pub fn bundles(&self) -> &X509BundleSet {
+        &self.bundles
+    }
+
+    pub fn federated_bundles(&self) -> &X509BundleSet {
         &self.federated_bundles
     }
 
     pub fn crl(&self) -> &Vec<CRL> {
         &self.crl
     }
+
+    pub fn summary(&self) -> Result<X509PayloadSummary> {
+        let mut svids = Vec::with_capacity(self.svids.len());
+        for svid in self.svids.iter() {
+            svids.push(svid.summary()?);
+        }
+
+        Ok(X509PayloadSummary {
+            svids,
+            federated_trust_domains: self.federated_bundles.trust_domains(),
+            crl_count: self.crl.len(),
+        })
+    }
 }
 
 pub type X509Stream = ::grpcio::ClientSStreamReceiver<X509SVIDResponse>;
@@ -76,20 +143,22 @@ impl X509Client {
         }
     }
 
-    pub fn fetch(&self, timeout: Option<Duration>) -> Result<Result<X509Payload>> {
-        let mut metadata = ::grpcio::MetadataBuilder::new();
-        metadata
-            .add_str("workload.spiffe.io", "true")
-            .chain_err(|| ErrorKind::ClientConfigFailure)?;
+    /// A client for the Workload API at `SPIFFE_ENDPOINT_SOCKET`.
+    pub fn from_env(backoff: Option<Duration>) -> Result<X509Client> {
+        Ok(X509Client::with_address(
+            &EndpointAddress::from_env()?,
+            backoff,
+        ))
+    }
 
-        let options = ::grpcio::CallOption::default()
-            .timeout(timeout.unwrap_or(*INITIAL_CONNECTION_TIMEOUT))
-            .headers(metadata.build());
+    /// A client for the Workload API at `addr`, which unlike the address given to `new` has been
+    /// validated.
+    pub fn with_address(addr: &EndpointAddress, backoff: Option<Duration>) -> X509Client {
+        X509Client::new(&addr.target(), backoff)
+    }
 
-        let mut rx = self
-            .client
-            .fetch_x509_svid_opt(&X509SVIDRequest::new(), options)
-            .chain_err(|| ErrorKind::ClientConfigFailure)?;
+    pub fn fetch(&self, timeout: Option<Duration>) -> Result<Result<X509Payload>> {
+        let mut rx = self.stream(timeout)?;
 
         let item = block_on(rx.next());
 
@@ -100,15 +169,36 @@ impl X509Client {
         }
     }
 
+    /// Like `fetch`, without blocking the calling thread.
+    pub async fn fetch_async(&self, timeout: Option<Duration>) -> Result<X509Payload> {
+        let mut rx = self.stream_async(timeout)?;
+
+        match rx.next().await {
+            Some(payload) => payload,
+            None => Err(ErrorKind::FetchFailure.into()),
+        }
+    }
+
     pub fn stream(&self, timeout: Option<Duration>) -> Result<X509Stream> {
+        self.open_stream(Some(timeout.unwrap_or(*INITIAL_CONNECTION_TIMEOUT)))
+    }
+
+    /// Like `stream`, with the responses parsed.
+    pub fn stream_async(&self, timeout: Option<Duration>) -> Result<X509PayloadStream> {
+        Ok(self.stream(timeout)?.map(payload))
+    }
+
+    /// Opens a stream that only ends with the connection if there is no `deadline`.
+    pub(crate) fn open_stream(&self, deadline: Option<Duration>) -> Result<X509Stream> {
         let mut metadata = ::grpcio::MetadataBuilder::new();
         metadata
             .add_str("workload.spiffe.io", "true")
             .chain_err(|| ErrorKind::ClientConfigFailure)?;
 
-        let options = ::grpcio::CallOption::default()
-            .timeout(timeout.unwrap_or(*INITIAL_CONNECTION_TIMEOUT))
-            .headers(metadata.build());
+        let mut options = ::grpcio::CallOption::default().headers(metadata.build());
+        if let Some(deadline) = deadline {
+            options = options.timeout(deadline);
+        }
 
         let rx = self
             .client
@@ -118,3 +208,14 @@ impl X509Client {
         Ok(rx)
     }
 }
+
+/// Parsed X.509 payloads, see `X509Client::stream_async`.
+pub type X509PayloadStream =
+    stream::Map<X509Stream, fn(::grpcio::Result<X509SVIDResponse>) -> Result<X509Payload>>;
+
+pub(crate) fn payload(response: ::grpcio::Result<X509SVIDResponse>) -> Result<X509Payload> {
+    match response {
+        Ok(response) => X509Payload::new(response),
+        Err(e) => Err(Error::with_chain(e, ErrorKind::FetchFailure)),
+    }
+}
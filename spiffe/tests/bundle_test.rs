@@ -0,0 +1,154 @@
+extern crate openssl;
+extern crate spiffe;
+
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use spiffe::bundle::jwt::{JwtBundle, JwtBundleSet};
+use spiffe::bundle::x509::{X509Bundle, X509BundleSet};
+use spiffe::svid::x509::X509 as X509Doc;
+use spiffe::svid::SVID;
+use spiffe::uri::{TrustDomain, URI};
+use spiffe::workload::x509::{X509Payload, X509Response};
+use std::fs;
+use std::path::Path;
+
+static LEAF_CERTIFICATE_PATH: &str = "./tests/leaf.cert.pem";
+static INTERMEDIATE_CERTIFICATE_PATH: &str = "./tests/intermediate.cert.pem";
+
+fn certificate(path: &str) -> X509 {
+    X509::from_pem(&fs::read(path).unwrap()).unwrap()
+}
+
+fn trust_domain(name: &str) -> TrustDomain {
+    TrustDomain::new(name).unwrap()
+}
+
+#[test]
+fn bundle_from_pem() {
+    let mut pem = fs::read(INTERMEDIATE_CERTIFICATE_PATH).unwrap();
+    pem.push(b'\n');
+    pem.extend(fs::read(LEAF_CERTIFICATE_PATH).unwrap());
+
+    let bundle = X509Bundle::from_pem(trust_domain("dev.acme.com"), &pem).unwrap();
+    assert_eq!(bundle.authorities().len(), 2);
+    assert!(bundle.contains_authority(&certificate(INTERMEDIATE_CERTIFICATE_PATH)));
+    assert!(bundle.contains_authority(&certificate(LEAF_CERTIFICATE_PATH)));
+}
+
+#[test]
+fn bundle_from_invalid_pem() {
+    assert!(X509Bundle::from_pem(trust_domain("dev.acme.com"), b"not a pem").is_err());
+}
+
+#[test]
+fn bundle_add_remove_contains() {
+    let intermediate = certificate(INTERMEDIATE_CERTIFICATE_PATH);
+    let mut bundle = X509Bundle::new(trust_domain("dev.acme.com"));
+    assert!(!bundle.contains_authority(&intermediate));
+
+    assert!(bundle.add_authority(intermediate.clone()));
+    assert!(!bundle.add_authority(intermediate.clone()));
+    assert_eq!(bundle.authorities().len(), 1);
+    assert!(bundle.contains_authority(&intermediate));
+
+    assert!(!bundle.remove_authority(&certificate(LEAF_CERTIFICATE_PATH)));
+    assert!(bundle.remove_authority(&intermediate));
+    assert!(!bundle.remove_authority(&intermediate));
+    assert!(bundle.authorities().is_empty());
+}
+
+#[test]
+fn bundle_set_lookup() {
+    let mut set = X509BundleSet::new();
+    assert!(set.is_empty());
+
+    let dev = X509Bundle::from_authorities(
+        trust_domain("dev.acme.com"),
+        vec![certificate(INTERMEDIATE_CERTIFICATE_PATH)],
+    );
+    assert!(set.insert(dev).is_none());
+    assert!(set
+        .insert(X509Bundle::new(trust_domain("prod.acme.com")))
+        .is_none());
+    assert_eq!(set.len(), 2);
+    assert_eq!(
+        set.trust_domains(),
+        vec![trust_domain("dev.acme.com"), trust_domain("prod.acme.com")]
+    );
+
+    let id = "spiffe://dev.acme.com/path/service".parse::<URI>().unwrap();
+    assert_eq!(set.get_for_id(&id).unwrap().authorities().len(), 1);
+    let unknown = "spiffe://example.org/service".parse::<URI>().unwrap();
+    assert!(set.get_for_id(&unknown).is_none());
+
+    let replaced = set
+        .insert(X509Bundle::new(trust_domain("dev.acme.com")))
+        .unwrap();
+    assert_eq!(replaced.authorities().len(), 1);
+    assert!(set
+        .get(&trust_domain("dev.acme.com"))
+        .unwrap()
+        .authorities()
+        .is_empty());
+
+    assert!(set.remove(&trust_domain("prod.acme.com")).is_some());
+    assert!(!set.contains(&trust_domain("prod.acme.com")));
+    assert_eq!(set.iter().count(), 1);
+}
+
+#[test]
+fn jwt_bundle_set_lookup() {
+    let mut set = JwtBundleSet::new();
+    assert!(set.is_empty());
+
+    let mut dev = JwtBundle::new(trust_domain("dev.acme.com"));
+    let key = PKey::public_key_from_pem(&fs::read("./tests/jwt_ec.pub.pem").unwrap()).unwrap();
+    dev.add_authority("ec-key", key);
+    assert!(set.insert(dev).is_none());
+    assert!(set
+        .insert(JwtBundle::new(trust_domain("prod.acme.com")))
+        .is_none());
+    assert_eq!(
+        set.trust_domains(),
+        vec![trust_domain("dev.acme.com"), trust_domain("prod.acme.com")]
+    );
+
+    let id = "spiffe://dev.acme.com/path/service".parse::<URI>().unwrap();
+    assert!(set.get_for_id(&id).unwrap().contains_authority("ec-key"));
+    let unknown = "spiffe://example.org/service".parse::<URI>().unwrap();
+    assert!(set.get_for_id(&unknown).is_none());
+
+    assert!(set.remove(&trust_domain("prod.acme.com")).is_some());
+    assert!(!set.contains(&trust_domain("prod.acme.com")));
+    assert_eq!((&set).into_iter().count(), 1);
+}
+
+#[test]
+fn payload_builds_bundle_set() {
+    let leaf = SVID::<X509Doc>::from_path(Path::new(LEAF_CERTIFICATE_PATH), None, None).unwrap();
+    let intermediate = certificate(INTERMEDIATE_CERTIFICATE_PATH);
+
+    let mut response = X509Response::new();
+    response.mut_svids().push(Default::default());
+    let svid = &mut response.mut_svids()[0];
+    svid.x509_svid = leaf.leaf().to_der().unwrap();
+    svid.bundle = intermediate.to_der().unwrap();
+    response.federated_bundles.insert(
+        "spiffe://example.org".to_string(),
+        leaf.leaf().to_der().unwrap(),
+    );
+
+    let payload = X509Payload::new(response).unwrap();
+    assert_eq!(payload.svids().len(), 1);
+
+    let federated = payload.federated_bundles();
+    assert_eq!(federated.trust_domains(), vec![trust_domain("example.org")]);
+
+    let bundles = payload.bundles();
+    assert_eq!(
+        bundles.trust_domains(),
+        vec![trust_domain("dev.acme.com"), trust_domain("example.org")]
+    );
+    let own = bundles.get_for_id(payload.svids()[0].uri()).unwrap();
+    assert!(own.contains_authority(&intermediate));
+}
@@ -0,0 +1,188 @@
+//! Certificate fixtures shared by the integration tests. Each test binary uses only some of them.
+#![allow(dead_code)]
+
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::{BasicConstraints, KeyUsage, SubjectAlternativeName};
+use openssl::x509::{X509Name, X509};
+use spiffe::bundle::x509::X509Bundle;
+use spiffe::svid::x509::X509 as X509Doc;
+use spiffe::svid::SVID;
+use spiffe::uri::TrustDomain;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DAY: i64 = 86_400;
+
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+pub fn key() -> PKey<Private> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+}
+
+pub fn trust_domain() -> TrustDomain {
+    TrustDomain::new("example.org").unwrap()
+}
+
+/// The key usage bits of a certificate.
+pub enum Usage {
+    None,
+    DigitalSignature,
+    KeyCertSign,
+    CRLSign,
+    Authority,
+    All,
+}
+
+/// What `certificate` puts in a test certificate.
+pub struct Template<'a> {
+    pub subject: &'a str,
+    /// Self-signed if `None`.
+    pub issuer: Option<(&'a X509, &'a PKey<Private>)>,
+    pub ca: bool,
+    pub usage: Usage,
+    pub san: Option<&'a SubjectAlternativeName>,
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl<'a> Template<'a> {
+    /// A self-signed leaf allowed to sign data, valid for a day from now, without SANs.
+    pub fn leaf(subject: &'a str) -> Template<'a> {
+        Template {
+            subject,
+            issuer: None,
+            ca: false,
+            usage: Usage::DigitalSignature,
+            san: None,
+            not_before: now(),
+            not_after: now() + DAY,
+        }
+    }
+
+    /// A self-signed CA allowed to sign certificates and CRLs, valid for a day from now, without
+    /// SANs.
+    pub fn ca(subject: &'a str) -> Template<'a> {
+        Template {
+            ca: true,
+            usage: Usage::Authority,
+            ..Template::leaf(subject)
+        }
+    }
+}
+
+/// Mints a certificate for `key` from `template`.
+pub fn certificate(template: &Template, key: &PKey<Private>) -> X509 {
+    let mut name = X509Name::builder().unwrap();
+    name.append_entry_by_text("CN", template.subject).unwrap();
+    let name = name.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+    builder.set_serial_number(&serial).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder
+        .set_not_before(&Asn1Time::from_unix(template.not_before as _).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::from_unix(template.not_after as _).unwrap())
+        .unwrap();
+    builder.set_pubkey(key).unwrap();
+
+    let signer = match template.issuer {
+        Some((cert, issuer_key)) => {
+            builder.set_issuer_name(cert.subject_name()).unwrap();
+            issuer_key
+        }
+        None => {
+            builder.set_issuer_name(&name).unwrap();
+            key
+        }
+    };
+
+    let mut constraints = BasicConstraints::new();
+    if template.ca {
+        constraints.critical().ca();
+    }
+    builder
+        .append_extension(constraints.build().unwrap())
+        .unwrap();
+
+    let mut key_usage = KeyUsage::new();
+    let key_usage = match template.usage {
+        Usage::None => None,
+        Usage::DigitalSignature => Some(key_usage.digital_signature()),
+        Usage::KeyCertSign => Some(key_usage.key_cert_sign()),
+        Usage::CRLSign => Some(key_usage.digital_signature().crl_sign()),
+        Usage::Authority => Some(key_usage.key_cert_sign().crl_sign()),
+        Usage::All => Some(key_usage.digital_signature().key_cert_sign().crl_sign()),
+    };
+    if let Some(key_usage) = key_usage {
+        builder
+            .append_extension(key_usage.critical().build().unwrap())
+            .unwrap();
+    }
+
+    if let Some(san) = template.san {
+        let issuer = template.issuer.map(|(cert, _)| cert.as_ref());
+        let san = san.build(&builder.x509v3_context(issuer, None)).unwrap();
+        builder.append_extension(san).unwrap();
+    }
+
+    builder.sign(signer, MessageDigest::sha256()).unwrap();
+    builder.build()
+}
+
+/// A root CA for `example.org` that issues leaves directly.
+pub struct Authority {
+    pub root: X509,
+    pub key: PKey<Private>,
+}
+
+impl Authority {
+    pub fn new() -> Authority {
+        let key = key();
+        let root = certificate(
+            &Template {
+                san: Some(SubjectAlternativeName::new().uri("spiffe://example.org")),
+                ..Template::ca("Root")
+            },
+            &key,
+        );
+        Authority { root, key }
+    }
+
+    /// Issues a leaf with `san`, and its key.
+    pub fn issue(&self, san: &mut SubjectAlternativeName) -> (X509, PKey<Private>) {
+        let key = key();
+        let cert = certificate(
+            &Template {
+                issuer: Some((&self.root, &self.key)),
+                san: Some(san),
+                ..Template::leaf("Leaf")
+            },
+            &key,
+        );
+        (cert, key)
+    }
+
+    /// Issues an X.509-SVID for `id`, with its key.
+    pub fn svid(&self, id: &str) -> SVID<X509Doc> {
+        let (cert, key) = self.issue(SubjectAlternativeName::new().uri(id));
+        SVID::<X509Doc>::from_x509(cert, Some(key.private_key_to_der().unwrap()), None).unwrap()
+    }
+
+    pub fn bundle(&self) -> X509Bundle {
+        X509Bundle::from_authorities(trust_domain(), vec![self.root.clone()])
+    }
+}
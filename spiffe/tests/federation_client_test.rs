@@ -0,0 +1,284 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate openssl;
+extern crate spiffe;
+extern crate tokio;
+
+mod common;
+
+use common::{trust_domain, Authority};
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::X509;
+use spiffe::bundle::spiffe::SpiffeBundle;
+use spiffe::bundle::x509::X509Bundle;
+use spiffe::federation::client::{BundleEndpointClient, BundleEndpointProfile};
+use spiffe::federation::{Error, ErrorKind};
+use spiffe::uri::URI;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Builder;
+
+fn document(authority: &Authority, sequence: u64) -> String {
+    let mut bundle = SpiffeBundle::new(trust_domain());
+    bundle
+        .x509_bundle_mut()
+        .add_authority(authority.root.clone());
+    bundle.set_sequence(Some(sequence));
+    bundle.set_refresh_hint(Some(Duration::from_secs(1)));
+    bundle.to_json().unwrap()
+}
+
+/// Serves HTTPS on a local port with `cert`, answering the nth request with `respond(n)`.
+fn serve<F>(cert: X509, key: PKey<Private>, respond: F) -> u16
+where
+    F: Fn(u64) -> (u16, String) + Send + 'static,
+{
+    serve_on("127.0.0.1:0", cert, key, respond)
+}
+
+fn serve_on<F>(address: &str, cert: X509, key: PKey<Private>, respond: F) -> u16
+where
+    F: Fn(u64) -> (u16, String) + Send + 'static,
+{
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = TcpListener::bind(address).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let mut requests = 0;
+        for stream in listener.incoming() {
+            let mut stream = match acceptor.accept(stream.unwrap()) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            let (status, body) = respond(requests);
+            requests += 1;
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.shutdown();
+        }
+    });
+
+    port
+}
+
+fn spiffe_endpoint(authority: &Authority, endpoint_id: &str) -> u16 {
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().uri(endpoint_id));
+    let json = document(authority, 1);
+    serve(cert, key, move |_| (200, json.clone()))
+}
+
+fn spiffe_profile(endpoint_id: &str, bundle: X509Bundle) -> BundleEndpointProfile {
+    BundleEndpointProfile::HttpsSpiffe {
+        endpoint_id: endpoint_id.parse::<URI>().unwrap(),
+        bundle,
+    }
+}
+
+#[test]
+fn fetch_https_web() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().dns("localhost"));
+    let json = document(&authority, 7);
+    let port = serve(cert, key, move |_| (200, json.clone()));
+
+    let url = format!("https://localhost:{}/bundle", port);
+    let mut client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    client.set_web_roots(vec![authority.root.clone()]);
+
+    let bundle = client.fetch().unwrap();
+    assert_eq!(bundle.trust_domain(), &trust_domain());
+    assert_eq!(bundle.sequence(), Some(7));
+    assert!(bundle.x509_bundle().contains_authority(&authority.root));
+}
+
+#[test]
+fn fetch_https_web_ipv6() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().ip("::1"));
+    let json = document(&authority, 7);
+    let port = serve_on("[::1]:0", cert, key, move |_| (200, json.clone()));
+
+    let url = format!("https://[::1]:{}/bundle", port);
+    let mut client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    client.set_web_roots(vec![authority.root.clone()]);
+
+    let bundle = client.fetch().unwrap();
+    assert_eq!(bundle.sequence(), Some(7));
+}
+
+#[test]
+fn fetch_async_within_runtime() {
+    let authority = Authority::new();
+    let port = spiffe_endpoint(&authority, "spiffe://example.org/bundle-endpoint");
+
+    let url = format!("https://127.0.0.1:{}/", port);
+    let profile = spiffe_profile("spiffe://example.org/bundle-endpoint", authority.bundle());
+    let client = BundleEndpointClient::new(&url, trust_domain(), profile).unwrap();
+
+    let mut runtime = Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let bundle = runtime.block_on(client.fetch_async()).unwrap();
+    assert_eq!(bundle.sequence(), Some(1));
+}
+
+#[test]
+fn fetch_https_web_untrusted() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().dns("localhost"));
+    let port = serve(cert, key, |_| (200, String::new()));
+
+    // The test authority is not a system WebPKI authority
+    let url = format!("https://localhost:{}/", port);
+    let client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    assert_matches!(
+        client.fetch(),
+        Err(Error(ErrorKind::TLSHandshakeFailure(_), _))
+    );
+}
+
+#[test]
+fn fetch_https_web_wrong_host() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().dns("example.org"));
+    let port = serve(cert, key, |_| (200, String::new()));
+
+    let url = format!("https://localhost:{}/", port);
+    let mut client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    client.set_web_roots(vec![authority.root.clone()]);
+    assert_matches!(
+        client.fetch(),
+        Err(Error(ErrorKind::TLSHandshakeFailure(_), _))
+    );
+}
+
+#[test]
+fn fetch_https_spiffe() {
+    let authority = Authority::new();
+    let port = spiffe_endpoint(&authority, "spiffe://example.org/bundle-endpoint");
+
+    let url = format!("https://127.0.0.1:{}/", port);
+    let profile = spiffe_profile("spiffe://example.org/bundle-endpoint", authority.bundle());
+    let client = BundleEndpointClient::new(&url, trust_domain(), profile).unwrap();
+
+    let bundle = client.fetch().unwrap();
+    assert_eq!(bundle.sequence(), Some(1));
+    assert!(bundle.x509_bundle().contains_authority(&authority.root));
+}
+
+#[test]
+fn fetch_https_spiffe_unexpected_id() {
+    let authority = Authority::new();
+    let port = spiffe_endpoint(&authority, "spiffe://example.org/impostor");
+
+    let url = format!("https://127.0.0.1:{}/", port);
+    let profile = spiffe_profile("spiffe://example.org/bundle-endpoint", authority.bundle());
+    let client = BundleEndpointClient::new(&url, trust_domain(), profile).unwrap();
+
+    assert_matches!(
+        client.fetch(),
+        Err(Error(ErrorKind::UnexpectedEndpointId(ref expected, ref actual), _))
+            if expected == "spiffe://example.org/bundle-endpoint"
+                && actual == "spiffe://example.org/impostor"
+    );
+}
+
+#[test]
+fn fetch_https_spiffe_unknown_authority() {
+    let authority = Authority::new();
+    let port = spiffe_endpoint(&authority, "spiffe://example.org/bundle-endpoint");
+
+    let url = format!("https://127.0.0.1:{}/", port);
+    let profile = spiffe_profile(
+        "spiffe://example.org/bundle-endpoint",
+        Authority::new().bundle(),
+    );
+    let client = BundleEndpointClient::new(&url, trust_domain(), profile).unwrap();
+
+    assert_matches!(
+        client.fetch(),
+        Err(Error(ErrorKind::EndpointAuthenticationFailure(_), _))
+    );
+}
+
+#[test]
+fn fetch_unexpected_status() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().dns("localhost"));
+    let port = serve(cert, key, |_| (404, String::new()));
+
+    let url = format!("https://localhost:{}/", port);
+    let mut client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    client.set_web_roots(vec![authority.root.clone()]);
+
+    assert_matches!(
+        client.fetch(),
+        Err(Error(ErrorKind::UnexpectedStatus(404), _))
+    );
+}
+
+#[test]
+fn client_invalid_url() {
+    for url in &["http://example.org/bundle", "https:///bundle", "not a url"] {
+        assert_matches!(
+            BundleEndpointClient::new(url, trust_domain(), BundleEndpointProfile::HttpsWeb),
+            Err(Error(ErrorKind::InvalidEndpointURL(_), _))
+        );
+    }
+}
+
+#[test]
+fn watch_publishes_refreshed_bundles() {
+    let authority = Authority::new();
+    let (cert, key) =
+        authority.issue(SubjectAlternativeName::new().uri("spiffe://example.org/bundle-endpoint"));
+    let documents: Vec<String> = (0..3).map(|n| document(&authority, n / 2)).collect();
+    // The second response repeats the first, so only two distinct bundles are published
+    let port = serve(cert, key, move |n| {
+        (200, documents[(n as usize).min(2)].clone())
+    });
+
+    let url = format!("https://127.0.0.1:{}/", port);
+    let profile = spiffe_profile("spiffe://example.org/bundle-endpoint", authority.bundle());
+    let client = BundleEndpointClient::new(&url, trust_domain(), profile).unwrap();
+    let watcher = client.watch(None).unwrap();
+
+    let updates = watcher.subscribe();
+    let timeout = Duration::from_secs(10);
+    assert_eq!(updates.recv_timeout(timeout).unwrap().sequence(), Some(0));
+    assert_eq!(updates.recv_timeout(timeout).unwrap().sequence(), Some(1));
+    assert_eq!(watcher.latest().unwrap().sequence(), Some(1));
+
+    // Late subscribers start with the latest bundle
+    let late = watcher.subscribe();
+    assert_eq!(late.recv_timeout(timeout).unwrap().sequence(), Some(1));
+}
@@ -0,0 +1,251 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate futures;
+extern crate openssl;
+extern crate spiffe;
+
+mod common;
+
+use common::{trust_domain, Authority};
+use futures::stream;
+use openssl::x509::extension::SubjectAlternativeName;
+use spiffe::bundle::spiffe::SpiffeBundle;
+use spiffe::bundle::x509::X509Bundle;
+use spiffe::federation::client::{BundleEndpointClient, BundleEndpointProfile};
+use spiffe::federation::server::{BundleEndpointHandle, BundleEndpointServer};
+use spiffe::federation::{Error, ErrorKind};
+use spiffe::svid::x509::X509 as X509Doc;
+use spiffe::svid::SVID;
+use spiffe::uri::{TrustDomain, URI};
+use spiffe::workload::x509::{X509Payload, X509Response};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ENDPOINT_ID: &str = "spiffe://example.org/bundle-endpoint";
+
+fn start(server: &BundleEndpointServer) -> BundleEndpointHandle {
+    server.start(&"127.0.0.1:0".parse().unwrap()).unwrap()
+}
+
+/// A Workload API payload with an SVID for the endpoint from `authority`, and its bundle.
+fn bundle_payload(authority: &Authority) -> X509Payload {
+    let mut response = X509Response::new();
+    response.mut_svids().push(Default::default());
+    let svid = &mut response.mut_svids()[0];
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().uri(ENDPOINT_ID));
+    svid.spiffe_id = ENDPOINT_ID.to_string();
+    svid.x509_svid = cert.to_der().unwrap();
+    svid.x509_svid_key = key.private_key_to_der().unwrap();
+    svid.bundle = authority.root.to_der().unwrap();
+    X509Payload::new(response).unwrap()
+}
+
+fn spiffe_client(handle: &BundleEndpointHandle, bundle: X509Bundle) -> BundleEndpointClient {
+    let url = format!("https://{}/", handle.local_addr());
+    let profile = BundleEndpointProfile::HttpsSpiffe {
+        endpoint_id: ENDPOINT_ID.parse::<URI>().unwrap(),
+        bundle,
+    };
+    BundleEndpointClient::new(&url, trust_domain(), profile).unwrap()
+}
+
+#[test]
+fn serve_https_spiffe() {
+    let authority = Authority::new();
+    let server = BundleEndpointServer::https_spiffe(
+        trust_domain(),
+        &authority.svid(ENDPOINT_ID),
+        Some(Duration::from_secs(60)),
+    )
+    .unwrap();
+    server.set_x509_bundle(authority.bundle()).unwrap();
+
+    let handle = start(&server);
+    let bundle = spiffe_client(&handle, authority.bundle()).fetch().unwrap();
+
+    assert_eq!(bundle.sequence(), Some(1));
+    assert_eq!(bundle.refresh_hint(), Some(Duration::from_secs(60)));
+    assert!(bundle.x509_bundle().contains_authority(&authority.root));
+}
+
+#[test]
+fn serve_https_web() {
+    let authority = Authority::new();
+    let (cert, key) = authority.issue(SubjectAlternativeName::new().dns("localhost"));
+    let identity =
+        X509Doc::from_chain(cert, vec![], Some(key.private_key_to_der().unwrap()), None).unwrap();
+    let server = BundleEndpointServer::https_web(trust_domain(), &identity, None).unwrap();
+    server.set_x509_bundle(authority.bundle()).unwrap();
+
+    let handle = start(&server);
+    let url = format!("https://localhost:{}/", handle.local_addr().port());
+    let mut client =
+        BundleEndpointClient::new(&url, trust_domain(), BundleEndpointProfile::HttpsWeb).unwrap();
+    client.set_web_roots(vec![authority.root.clone()]);
+
+    let bundle = client.fetch().unwrap();
+    assert_eq!(bundle.refresh_hint(), Some(Duration::from_secs(300)));
+    assert!(bundle.x509_bundle().contains_authority(&authority.root));
+}
+
+#[test]
+fn serve_unavailable_without_bundle() {
+    let authority = Authority::new();
+    let server =
+        BundleEndpointServer::https_spiffe(trust_domain(), &authority.svid(ENDPOINT_ID), None)
+            .unwrap();
+
+    let handle = start(&server);
+    assert_matches!(
+        spiffe_client(&handle, authority.bundle()).fetch(),
+        Err(Error(ErrorKind::UnexpectedStatus(503), _))
+    );
+}
+
+#[test]
+fn sequence_follows_changes() {
+    let authority = Authority::new();
+    let server =
+        BundleEndpointServer::https_spiffe(trust_domain(), &authority.svid(ENDPOINT_ID), None)
+            .unwrap();
+    let sequence = || server.bundle().unwrap().sequence();
+
+    server.set_x509_bundle(authority.bundle()).unwrap();
+    assert_eq!(sequence(), Some(1));
+
+    // Unchanged authorities keep the sequence number
+    server.set_x509_bundle(authority.bundle()).unwrap();
+    assert_eq!(sequence(), Some(1));
+
+    let mut rotated = authority.bundle();
+    rotated.add_authority(Authority::new().root);
+    server.set_x509_bundle(rotated).unwrap();
+    assert_eq!(sequence(), Some(2));
+
+    // A sequence number ahead of the server's is kept
+    let mut bundle = SpiffeBundle::new(trust_domain());
+    bundle.set_sequence(Some(40));
+    server.set_bundle(bundle).unwrap();
+    assert_eq!(sequence(), Some(40));
+    assert!(server
+        .bundle()
+        .unwrap()
+        .x509_bundle()
+        .authorities()
+        .is_empty());
+
+    // One behind it is not
+    let mut bundle = SpiffeBundle::new(trust_domain());
+    bundle
+        .x509_bundle_mut()
+        .add_authority(authority.root.clone());
+    bundle.set_sequence(Some(3));
+    server.set_bundle(bundle).unwrap();
+    assert_eq!(sequence(), Some(41));
+}
+
+#[test]
+fn sequence_floor_applies_to_unchanged_bundle() {
+    let authority = Authority::new();
+    let server =
+        BundleEndpointServer::https_spiffe(trust_domain(), &authority.svid(ENDPOINT_ID), None)
+            .unwrap();
+    server.set_x509_bundle(authority.bundle()).unwrap();
+    assert_eq!(server.bundle().unwrap().sequence(), Some(1));
+
+    let mut bundle = SpiffeBundle::new(trust_domain());
+    bundle
+        .x509_bundle_mut()
+        .add_authority(authority.root.clone());
+    bundle.set_sequence(Some(9));
+    server.set_bundle(bundle).unwrap();
+    assert_eq!(server.bundle().unwrap().sequence(), Some(9));
+}
+
+#[test]
+fn reject_foreign_bundle() {
+    let authority = Authority::new();
+    let server =
+        BundleEndpointServer::https_spiffe(trust_domain(), &authority.svid(ENDPOINT_ID), None)
+            .unwrap();
+
+    let foreign = X509Bundle::new(TrustDomain::new("other.org").unwrap());
+    assert_matches!(
+        server.set_x509_bundle(foreign),
+        Err(Error(ErrorKind::Bundle(_), _))
+    );
+    assert!(server.bundle().is_none());
+}
+
+#[test]
+fn reject_identity_without_key() {
+    let authority = Authority::new();
+    let (cert, _) = authority.issue(SubjectAlternativeName::new().uri(ENDPOINT_ID));
+    let svid = SVID::<X509Doc>::from_x509(cert, None, None).unwrap();
+
+    assert_matches!(
+        BundleEndpointServer::https_spiffe(trust_domain(), &svid, None),
+        Err(Error(ErrorKind::MissingIdentityKey, _))
+    );
+}
+
+#[test]
+fn update_from_workload_payload() {
+    let authority = Authority::new();
+    let server = BundleEndpointServer::https_spiffe(
+        trust_domain(),
+        &authority.svid("spiffe://example.org/previous"),
+        None,
+    )
+    .unwrap();
+    let handle = start(&server);
+
+    server
+        .update_from_payload(&bundle_payload(&authority))
+        .unwrap();
+
+    // The endpoint now presents the payload's SVID and serves its bundle
+    let bundle = spiffe_client(&handle, authority.bundle()).fetch().unwrap();
+    assert_eq!(bundle.sequence(), Some(1));
+    assert!(bundle.x509_bundle().contains_authority(&authority.root));
+
+    let payload = X509Payload::new(X509Response::new()).unwrap();
+    assert_matches!(
+        server.update_from_payload(&payload),
+        Err(Error(ErrorKind::BundleNotFound(_), _))
+    );
+}
+
+#[test]
+fn follow_reopens_ended_stream() {
+    let first = Authority::new();
+    let second = Authority::new();
+    let server = BundleEndpointServer::https_web(
+        trust_domain(),
+        &first.svid("spiffe://example.org/endpoint"),
+        None,
+    )
+    .unwrap();
+
+    // Each stream ends after one payload, like a Workload API stream past its deadline
+    let mut payloads = vec![bundle_payload(&second), bundle_payload(&first)];
+    let _following = server
+        .follow_with(move || match payloads.pop() {
+            Some(payload) => Ok(stream::iter(vec![Ok(payload)])),
+            None => Err(spiffe::workload::ErrorKind::FetchFailure.into()),
+        })
+        .unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let served = server.bundle();
+        if let Some(bundle) = served.filter(|b| b.x509_bundle().contains_authority(&second.root)) {
+            assert!(!bundle.x509_bundle().contains_authority(&first.root));
+            assert_eq!(bundle.sequence(), Some(2));
+            break;
+        }
+        assert!(Instant::now() < deadline, "second payload was not served");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
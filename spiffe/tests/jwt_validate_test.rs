@@ -0,0 +1,246 @@
+#![cfg(feature = "testing")]
+
+#[macro_use]
+extern crate assert_matches;
+
+extern crate serde_json;
+extern crate spiffe;
+
+use serde_json::json;
+use spiffe::bundle::jwt::JwtBundleSet;
+use spiffe::svid::jwt::{Error, ErrorKind, Jwt};
+use spiffe::testing::jwt::{Algorithm, JWTTemplate, Quirk, Signer};
+use spiffe::uri::TrustDomain;
+use spiffe::workload;
+use spiffe::workload::jwt::{parse_bundles, validate_offline, JwtBundlesResponse};
+use std::time::{Duration, SystemTime};
+
+const SPIFFE_ID: &str = "spiffe://example.org/workload";
+const AUDIENCE: &str = "audience";
+
+fn signer(algorithm: Algorithm) -> Signer {
+    let mut signer = Signer::new(TrustDomain::new("example.org").unwrap());
+    signer.generate_key("key", algorithm).unwrap();
+    signer
+}
+
+fn jwt(signer: &Signer, template: JWTTemplate) -> Jwt {
+    Jwt::new(signer.sign(&template).unwrap()).unwrap()
+}
+
+fn template() -> JWTTemplate {
+    JWTTemplate::new(SPIFFE_ID, &[AUDIENCE, "other"])
+}
+
+#[test]
+fn verify_algorithms() {
+    for algorithm in &[
+        Algorithm::ES256,
+        Algorithm::ES384,
+        Algorithm::ES512,
+        Algorithm::RS256,
+        Algorithm::RS384,
+        Algorithm::RS512,
+        Algorithm::PS256,
+        Algorithm::PS384,
+        Algorithm::PS512,
+    ] {
+        let signer = signer(*algorithm);
+        let jwt = jwt(&signer, template());
+        assert_eq!(jwt.header().alg(), algorithm.name());
+
+        let id = jwt.verify(&signer.bundle().unwrap(), AUDIENCE).unwrap();
+        assert_eq!(id.to_string(), SPIFFE_ID);
+    }
+}
+
+#[test]
+fn reject_expired() {
+    let signer = signer(Algorithm::ES256);
+    let jwt = jwt(
+        &signer,
+        template().expiry(SystemTime::now() - Duration::from_secs(1)),
+    );
+    assert_matches!(
+        jwt.verify(&signer.bundle().unwrap(), AUDIENCE),
+        Err(Error(ErrorKind::TokenExpired, _))
+    );
+}
+
+#[test]
+fn reject_audience() {
+    let signer = signer(Algorithm::ES256);
+    assert_matches!(
+        jwt(&signer, template()).verify(&signer.bundle().unwrap(), "stranger"),
+        Err(Error(ErrorKind::AudienceMismatch(ref aud), _)) if aud == "stranger"
+    );
+}
+
+#[test]
+fn reject_bad_signature() {
+    let signer = signer(Algorithm::RS256);
+    let bundle = signer.bundle().unwrap();
+    assert_matches!(
+        jwt(&signer, template().quirk(Quirk::BadSignature)).verify(&bundle, AUDIENCE),
+        Err(Error(ErrorKind::InvalidSignature, _))
+    );
+
+    // Signed by another key under the same key ID
+    let impostor = self::signer(Algorithm::RS256);
+    assert_matches!(
+        jwt(&impostor, template()).verify(&bundle, AUDIENCE),
+        Err(Error(ErrorKind::InvalidSignature, _))
+    );
+}
+
+#[test]
+fn reject_key_ids() {
+    let mut signer = signer(Algorithm::ES256);
+    signer.generate_key("next", Algorithm::ES256).unwrap();
+
+    let jwt = jwt(&signer, template().key_id("next"));
+    let mut bundle = signer.bundle().unwrap();
+    bundle.remove_authority("next");
+    assert_matches!(
+        jwt.verify(&bundle, AUDIENCE),
+        Err(Error(ErrorKind::UnknownKeyId(ref kid), _)) if kid == "next"
+    );
+
+    let jwt = self::jwt(&signer, template().quirk(Quirk::NoKid));
+    assert_matches!(
+        jwt.verify(&signer.bundle().unwrap(), AUDIENCE),
+        Err(Error(ErrorKind::MissingKeyId, _))
+    );
+}
+
+#[test]
+fn reject_algorithms() {
+    let signer = signer(Algorithm::ES256);
+    let bundle = signer.bundle().unwrap();
+    let verify = |alg: &str| {
+        jwt(&signer, template().quirk(Quirk::Alg(alg.to_string()))).verify(&bundle, AUDIENCE)
+    };
+
+    for alg in &["none", "HS256", "ES256K", ""] {
+        assert_matches!(
+            verify(alg),
+            Err(Error(ErrorKind::UnsupportedAlgorithm(_), _))
+        );
+    }
+    // Wrong key type, then wrong curve
+    for alg in &["RS256", "PS256", "ES384"] {
+        assert_matches!(
+            verify(alg),
+            Err(Error(ErrorKind::KeyAlgorithmMismatch(_, _), _))
+        );
+    }
+}
+
+#[test]
+fn reject_type() {
+    let signer = signer(Algorithm::ES256);
+    let token = signer.sign(&template()).unwrap();
+
+    // {"alg":"ES256","kid":"key","typ":"secevent+jwt"}
+    let header = "eyJhbGciOiJFUzI1NiIsImtpZCI6ImtleSIsInR5cCI6InNlY2V2ZW50K2p3dCJ9";
+    let token = format!("{}{}", header, &token[token.find('.').unwrap()..]);
+    assert_matches!(
+        Jwt::new(token).unwrap().verify(&signer.bundle().unwrap(), AUDIENCE),
+        Err(Error(ErrorKind::UnsupportedType(ref typ), _)) if typ == "secevent+jwt"
+    );
+}
+
+#[test]
+fn reject_foreign_bundle() {
+    let signer = signer(Algorithm::ES256);
+    let foreign = Signer::new(TrustDomain::new("other.org").unwrap());
+    assert_matches!(
+        jwt(&signer, template()).verify(&foreign.bundle().unwrap(), AUDIENCE),
+        Err(Error(ErrorKind::TrustDomainMismatch(_, _), _))
+    );
+}
+
+#[test]
+fn validate_offline_response() {
+    let signer = signer(Algorithm::ES256);
+    let jwt = jwt(&signer, template().claim("group", json!(["admins", 7])));
+    let mut bundles = JwtBundleSet::new();
+    bundles.insert(signer.bundle().unwrap());
+
+    let response = validate_offline(AUDIENCE, &jwt, &bundles).unwrap();
+    assert_eq!(response.spiffe_id().to_string(), SPIFFE_ID);
+
+    let claims = response.claims().unwrap().get_fields();
+    assert_eq!(claims["sub"].get_string_value(), SPIFFE_ID);
+    let aud = claims["aud"].get_list_value().get_values();
+    assert_eq!(aud.len(), 2);
+    assert_eq!(aud[0].get_string_value(), AUDIENCE);
+    assert!(claims["exp"].get_number_value() > 0.0);
+    let group = claims["group"].get_list_value().get_values();
+    assert_eq!(group[0].get_string_value(), "admins");
+    assert_eq!(group[1].get_number_value(), 7.0);
+
+    assert_matches!(
+        validate_offline("stranger", &jwt, &bundles),
+        Err(workload::Error(workload::ErrorKind::ValidateFailure, _))
+    );
+    assert_matches!(
+        validate_offline(AUDIENCE, &jwt, &JwtBundleSet::new()),
+        Err(workload::Error(workload::ErrorKind::ValidateFailure, _))
+    );
+}
+
+#[test]
+fn parse_bundles_response() {
+    let signer = signer(Algorithm::RS256);
+    let mut federated = Signer::new(TrustDomain::new("other.org").unwrap());
+    federated.generate_key("other", Algorithm::ES384).unwrap();
+
+    let mut response = JwtBundlesResponse::new();
+    response.bundles.insert(
+        "spiffe://example.org".to_string(),
+        signer.jwks().unwrap().into_bytes(),
+    );
+    response.bundles.insert(
+        "other.org".to_string(),
+        federated.jwks().unwrap().into_bytes(),
+    );
+
+    let bundles = parse_bundles(response).unwrap();
+    assert_eq!(
+        bundles.trust_domains(),
+        vec![
+            TrustDomain::new("example.org").unwrap(),
+            TrustDomain::new("other.org").unwrap()
+        ]
+    );
+    let other = bundles
+        .get(&TrustDomain::new("other.org").unwrap())
+        .unwrap();
+    assert!(other.contains_authority("other"));
+
+    let jwt = jwt(&signer, template());
+    let response = validate_offline(AUDIENCE, &jwt, &bundles).unwrap();
+    assert_eq!(response.spiffe_id().to_string(), SPIFFE_ID);
+}
+
+#[test]
+fn parse_bundles_rejects_invalid_jwks() {
+    let mut response = JwtBundlesResponse::new();
+    response
+        .bundles
+        .insert("example.org".to_string(), b"{\"keys\": 7}".to_vec());
+    assert_matches!(
+        parse_bundles(response),
+        Err(workload::Error(workload::ErrorKind::Bundle(_), _))
+    );
+
+    let mut response = JwtBundlesResponse::new();
+    response
+        .bundles
+        .insert("spiffe://".to_string(), b"{\"keys\": []}".to_vec());
+    assert_matches!(
+        parse_bundles(response),
+        Err(workload::Error(workload::ErrorKind::Uri(_), _))
+    );
+}
@@ -0,0 +1,112 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate spiffe;
+
+use spiffe::uri::{Error, ErrorKind, SpiffeIdPattern, URI};
+
+fn id(id: &str) -> URI {
+    id.parse::<URI>().unwrap()
+}
+
+#[test]
+fn literal_pattern() {
+    let pattern = "spiffe://example.org/ns/prod"
+        .parse::<SpiffeIdPattern>()
+        .unwrap();
+    assert!(pattern.is_match(&id("spiffe://example.org/ns/prod")));
+    assert!(!pattern.is_match(&id("spiffe://example.org/ns/prod/sa")));
+    assert!(!pattern.is_match(&id("spiffe://example.com/ns/prod")));
+}
+
+#[test]
+fn named_capture_and_wildcard() {
+    let pattern = SpiffeIdPattern::new("spiffe://prod.acme.com/ns/{namespace}/sa/*").unwrap();
+    assert_eq!(pattern.capture_names(), &["namespace".to_string()]);
+
+    let matched = id("spiffe://prod.acme.com/ns/billing/sa/api");
+    let captures = pattern.captures(&matched).unwrap();
+    assert_eq!(captures.get("namespace"), Some("billing"));
+    assert_eq!(captures.len(), 1);
+
+    assert!(!pattern.is_match(&id("spiffe://prod.acme.com/ns/billing/sa")));
+    assert!(!pattern.is_match(&id("spiffe://prod.acme.com/ns/billing/sa/api/x")));
+    assert!(!pattern.is_match(&id("spiffe://prod.acme.com/ns/billing/role/api")));
+}
+
+#[test]
+fn trust_domain_wildcard_and_recursive_path() {
+    let pattern = SpiffeIdPattern::new("spiffe://*.acme.com/**").unwrap();
+    assert!(pattern.is_match(&id("spiffe://prod.acme.com/a")));
+    assert!(pattern.is_match(&id("spiffe://dev.acme.com/a/b/c")));
+    assert!(!pattern.is_match(&id("spiffe://acme.com/a")));
+    assert!(!pattern.is_match(&id("spiffe://a.b.acme.com/a")));
+    assert!(!pattern.is_match(&id("spiffe://prod.acme.org/a")));
+}
+
+#[test]
+fn recursive_in_the_middle() {
+    let pattern = SpiffeIdPattern::new("spiffe://example.org/ns/**/{service}").unwrap();
+    let matched = id("spiffe://example.org/ns/a/b/api");
+    assert_eq!(
+        pattern.captures(&matched).unwrap().get("service"),
+        Some("api")
+    );
+    let matched = id("spiffe://example.org/ns/api");
+    assert_eq!(
+        pattern.captures(&matched).unwrap().get("service"),
+        Some("api")
+    );
+    assert!(!pattern.is_match(&id("spiffe://example.org/ns")));
+}
+
+#[test]
+fn many_recursive_parts_over_many_segments() {
+    let pattern = format!("spiffe://example.org/{}b", "**/a/".repeat(12));
+    let pattern = SpiffeIdPattern::new(&pattern).unwrap();
+
+    let path = "a/".repeat(300);
+    assert!(!pattern.is_match(&id(&format!("spiffe://example.org/{}c", path))));
+    assert!(pattern.is_match(&id(&format!("spiffe://example.org/{}b", path))));
+}
+
+#[test]
+fn trust_domain_capture() {
+    let pattern = SpiffeIdPattern::new("spiffe://{env}.acme.com/{workload}").unwrap();
+    let matched = id("spiffe://staging.acme.com/api");
+    let captures = pattern.captures(&matched).unwrap();
+    assert_eq!(captures.get("env"), Some("staging"));
+    assert_eq!(captures.get("workload"), Some("api"));
+    assert_eq!(captures.get("missing"), None);
+}
+
+#[test]
+fn captures_borrowed_id() {
+    let pattern = SpiffeIdPattern::new("spiffe://example.org/sa/{name}").unwrap();
+    let matched = spiffe::uri::SpiffeIdRef::new("spiffe://example.org/sa/api").unwrap();
+    assert_eq!(
+        pattern.captures_ref(matched).unwrap().get("name"),
+        Some("api")
+    );
+}
+
+#[test]
+fn invalid_patterns() {
+    for pattern in &[
+        "http://example.org/path",
+        "spiffe://example.org",
+        "spiffe://example.org/",
+        "spiffe://Example.org/path",
+        "spiffe://example.org/a//b",
+        "spiffe://example.org/a/../b",
+        "spiffe://example.org/pre*",
+        "spiffe://**.example.org/path",
+        "spiffe://example.org/{a}/{a}",
+        "spiffe://example.org/{a-b}",
+    ] {
+        assert_matches!(
+            SpiffeIdPattern::new(pattern),
+            Err(Error(ErrorKind::InvalidPattern(_, _), _))
+        );
+    }
+}
@@ -0,0 +1,159 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate openssl;
+extern crate spiffe;
+
+mod common;
+
+use common::{key, Template, Usage};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::X509;
+use spiffe::svid::x509::{validate_signing_certificate, Error, ErrorKind, X509 as X509Doc};
+use spiffe::svid::SVID;
+
+/// Mints a self-signed certificate; SVID parsing does not check signatures, only the profile.
+fn certificate(ca: bool, usage: Usage, uris: &[&str]) -> X509 {
+    let mut san = SubjectAlternativeName::new();
+    for uri in uris {
+        san.uri(uri);
+    }
+
+    let template = Template {
+        ca,
+        usage,
+        san: if uris.is_empty() { None } else { Some(&san) },
+        ..Template::leaf("Profile")
+    };
+    common::certificate(&template, &key())
+}
+
+fn parse_leaf(cert: X509) -> Result<SVID<X509Doc>, Error> {
+    SVID::<X509Doc>::from_x509(cert, None, None)
+}
+
+#[test]
+fn leaf_valid_profile() {
+    let cert = certificate(
+        false,
+        Usage::DigitalSignature,
+        &["spiffe://example.org/service"],
+    );
+    let svid = parse_leaf(cert).unwrap();
+    assert_eq!(svid.uri().to_string(), "spiffe://example.org/service");
+}
+
+#[test]
+fn leaf_is_ca() {
+    let cert = certificate(
+        true,
+        Usage::DigitalSignature,
+        &["spiffe://example.org/service"],
+    );
+    assert_matches!(parse_leaf(cert), Err(Error(ErrorKind::LeafIsCA, _)));
+}
+
+#[test]
+fn leaf_missing_key_usage() {
+    let cert = certificate(false, Usage::None, &["spiffe://example.org/service"]);
+    assert_matches!(
+        parse_leaf(cert),
+        Err(Error(ErrorKind::LeafMissingDigitalSignature, _))
+    );
+}
+
+#[test]
+fn leaf_missing_digital_signature() {
+    let cert = certificate(false, Usage::KeyCertSign, &["spiffe://example.org/service"]);
+    assert_matches!(
+        parse_leaf(cert),
+        Err(Error(ErrorKind::LeafMissingDigitalSignature, _))
+    );
+}
+
+#[test]
+fn leaf_has_key_cert_sign() {
+    let cert = certificate(false, Usage::All, &["spiffe://example.org/service"]);
+    assert_matches!(
+        parse_leaf(cert),
+        Err(Error(ErrorKind::LeafHasKeyCertSign, _))
+    );
+}
+
+#[test]
+fn leaf_has_crl_sign() {
+    let cert = certificate(false, Usage::CRLSign, &["spiffe://example.org/service"]);
+    assert_matches!(parse_leaf(cert), Err(Error(ErrorKind::LeafHasCRLSign, _)));
+}
+
+#[test]
+fn leaf_multiple_uri_sans() {
+    let cert = certificate(
+        false,
+        Usage::DigitalSignature,
+        &["spiffe://example.org/a", "spiffe://example.org/b"],
+    );
+    assert_matches!(
+        parse_leaf(cert),
+        Err(Error(ErrorKind::MultipleURIFound(ref first, ref next), _))
+            if first == "spiffe://example.org/a" && next == "spiffe://example.org/b"
+    );
+}
+
+#[test]
+fn leaf_non_spiffe_uri_san() {
+    let cert = certificate(false, Usage::DigitalSignature, &["https://example.org/a"]);
+    assert_matches!(
+        parse_leaf(cert),
+        Err(Error(ErrorKind::NonSpiffeURI(ref uri), _)) if uri == "https://example.org/a"
+    );
+}
+
+#[test]
+fn leaf_without_uri_san() {
+    let cert = certificate(false, Usage::DigitalSignature, &[]);
+    assert_matches!(parse_leaf(cert), Err(Error(ErrorKind::InvalidSAN, _)));
+}
+
+#[test]
+fn signing_certificate_valid_profile() {
+    let cert = certificate(true, Usage::All, &["spiffe://example.org"]);
+    let trust_domain = validate_signing_certificate(&cert).unwrap();
+    assert_eq!(trust_domain, "example.org");
+}
+
+#[test]
+fn signing_certificate_not_ca() {
+    let cert = certificate(false, Usage::All, &["spiffe://example.org"]);
+    assert_matches!(
+        validate_signing_certificate(&cert),
+        Err(Error(ErrorKind::SigningCertNotCA, _))
+    );
+}
+
+#[test]
+fn signing_certificate_missing_key_cert_sign() {
+    let cert = certificate(true, Usage::DigitalSignature, &["spiffe://example.org"]);
+    assert_matches!(
+        validate_signing_certificate(&cert),
+        Err(Error(ErrorKind::SigningCertMissingKeyCertSign, _))
+    );
+}
+
+#[test]
+fn signing_certificate_missing_spiffe_id() {
+    let cert = certificate(true, Usage::All, &[]);
+    assert_matches!(
+        validate_signing_certificate(&cert),
+        Err(Error(ErrorKind::SigningCertMissingSpiffeId, _))
+    );
+}
+
+#[test]
+fn signing_certificate_id_has_path() {
+    let cert = certificate(true, Usage::All, &["spiffe://example.org/ca"]);
+    assert_matches!(
+        validate_signing_certificate(&cert),
+        Err(Error(ErrorKind::SigningCertIdHasPath(ref id), _)) if id == "spiffe://example.org/ca"
+    );
+}
@@ -0,0 +1,37 @@
+#![cfg(all(feature = "mlock", target_os = "linux"))]
+
+#[macro_use]
+extern crate assert_matches;
+
+extern crate openssl;
+extern crate spiffe;
+
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use spiffe::svid::x509::{init_secure_heap, private_key_from_bytes, Error, ErrorKind};
+
+extern "C" {
+    // Not exposed by openssl-sys
+    fn CRYPTO_secure_used() -> usize;
+}
+
+// The secure heap is process-wide, so this binary holds a single test
+#[test]
+fn secure_heap_holds_parsed_keys() {
+    init_secure_heap(64 * 1024, 32).unwrap();
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let pem = key.private_key_to_pem_pkcs8().unwrap();
+
+    let used = unsafe { CRYPTO_secure_used() };
+    let parsed = private_key_from_bytes(&pem).unwrap();
+    assert!(parsed.public_eq(&key));
+    assert!(unsafe { CRYPTO_secure_used() } > used);
+
+    assert_matches!(
+        init_secure_heap(64 * 1024, 32),
+        Err(Error(ErrorKind::SecureHeapUnavailable, _))
+    );
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "serde")]
+
+extern crate serde_json;
+extern crate spiffe;
+
+use spiffe::svid::x509::{X509Summary, X509};
+use spiffe::svid::SVID;
+use spiffe::uri::{SpiffeIdPattern, TrustDomain, URI};
+use std::path::Path;
+
+static LEAF_CERTIFICATE_PATH: &str = "./tests/leaf.cert.pem";
+
+#[test]
+fn uri_round_trip() {
+    let id = "spiffe://example.org/service".parse::<URI>().unwrap();
+    let json = serde_json::to_string(&id).unwrap();
+    assert_eq!(json, r#""spiffe://example.org/service""#);
+    assert_eq!(serde_json::from_str::<URI>(&json).unwrap(), id);
+}
+
+#[test]
+fn uri_deserialize_validates() {
+    assert!(serde_json::from_str::<URI>(r#""spiffe://example.org/service/""#).is_err());
+    assert!(serde_json::from_str::<URI>(r#""https://example.org/service""#).is_err());
+    assert!(serde_json::from_str::<URI>("42").is_err());
+}
+
+#[test]
+fn uri_list_from_config() {
+    let ids: Vec<URI> =
+        serde_json::from_str(r#"["spiffe://example.org/a", "spiffe://example.org/b"]"#).unwrap();
+    assert_eq!(ids[1].path(), "/b");
+}
+
+#[test]
+fn trust_domain_round_trip() {
+    let td = TrustDomain::new("example.org").unwrap();
+    let json = serde_json::to_string(&td).unwrap();
+    assert_eq!(json, r#""example.org""#);
+    assert_eq!(serde_json::from_str::<TrustDomain>(&json).unwrap(), td);
+    assert_eq!(
+        serde_json::from_str::<TrustDomain>(r#""spiffe://example.org""#).unwrap(),
+        td
+    );
+    assert!(serde_json::from_str::<TrustDomain>(r#""Example.org""#).is_err());
+}
+
+#[test]
+fn pattern_round_trip() {
+    let json = r#""spiffe://example.org/ns/{namespace}/**""#;
+    let pattern = serde_json::from_str::<SpiffeIdPattern>(json).unwrap();
+    assert_eq!(serde_json::to_string(&pattern).unwrap(), json);
+    assert!(serde_json::from_str::<SpiffeIdPattern>(r#""spiffe://example.org""#).is_err());
+}
+
+#[test]
+fn x509_summary_round_trip() {
+    let svid = SVID::<X509>::from_path(Path::new(LEAF_CERTIFICATE_PATH), None, None).unwrap();
+    let summary = svid.summary().unwrap();
+
+    assert_eq!(summary.spiffe_id(), svid.uri());
+    assert_eq!(summary.serial(), "1000");
+    assert_eq!(summary.not_before(), 1_500_483_020);
+    assert_eq!(summary.not_after(), 1_501_347_020);
+    assert_eq!(
+        summary.chain_fingerprints(),
+        &["ba4d4a51793c070c363149021efaa6c4b1074ee7af348b369e2ccbbf9efae6b9".to_string()]
+    );
+
+    let json = serde_json::to_value(&summary).unwrap();
+    assert_eq!(json["spiffe_id"], "spiffe://dev.acme.com/path/service");
+    assert_eq!(json["not_after"], 1_501_347_020);
+
+    let decoded: X509Summary = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded, summary);
+}
@@ -0,0 +1,186 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate openssl;
+extern crate serde_json;
+extern crate spiffe;
+
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use serde_json::{json, Value};
+use spiffe::bundle::jwt::JwtBundle;
+use spiffe::bundle::spiffe::SpiffeBundle;
+use spiffe::bundle::x509::X509Bundle;
+use spiffe::bundle::{Error, ErrorKind};
+use spiffe::uri::TrustDomain;
+use std::fs;
+use std::time::Duration;
+
+static BUNDLE_PATH: &str = "./tests/spiffe_bundle.json";
+static INTERMEDIATE_CERTIFICATE_PATH: &str = "./tests/intermediate.cert.pem";
+static JWT_EC_KEY_PATH: &str = "./tests/jwt_ec.pub.pem";
+static JWT_RSA_KEY_PATH: &str = "./tests/jwt_rsa.pub.pem";
+
+fn trust_domain() -> TrustDomain {
+    TrustDomain::new("dev.acme.com").unwrap()
+}
+
+fn parse(json: &Value) -> Result<SpiffeBundle, Error> {
+    SpiffeBundle::parse(trust_domain(), json.to_string().as_bytes())
+}
+
+fn fixture() -> SpiffeBundle {
+    SpiffeBundle::parse(trust_domain(), &fs::read(BUNDLE_PATH).unwrap()).unwrap()
+}
+
+fn assert_same_contents(a: &SpiffeBundle, b: &SpiffeBundle) {
+    assert_eq!(a.trust_domain(), b.trust_domain());
+    assert_eq!(a.sequence(), b.sequence());
+    assert_eq!(a.refresh_hint(), b.refresh_hint());
+
+    let der = |bundle: &SpiffeBundle| -> Vec<Vec<u8>> {
+        bundle
+            .x509_bundle()
+            .authorities()
+            .iter()
+            .map(|a| a.to_der().unwrap())
+            .collect()
+    };
+    assert_eq!(der(a), der(b));
+
+    let jwt_a = a.jwt_bundle().authorities();
+    let jwt_b = b.jwt_bundle().authorities();
+    assert_eq!(jwt_a.len(), jwt_b.len());
+    for (key_id, key) in jwt_a {
+        assert!(key.public_eq(&jwt_b[key_id]));
+    }
+}
+
+#[test]
+fn parse_bundle() {
+    let bundle = fixture();
+    assert_eq!(bundle.trust_domain(), "dev.acme.com");
+    assert_eq!(bundle.sequence(), Some(12));
+    assert_eq!(bundle.refresh_hint(), Some(Duration::from_secs(300)));
+
+    let intermediate = X509::from_pem(&fs::read(INTERMEDIATE_CERTIFICATE_PATH).unwrap()).unwrap();
+    assert_eq!(bundle.x509_bundle().authorities().len(), 1);
+    assert!(bundle.x509_bundle().contains_authority(&intermediate));
+
+    // The key with an unknown use is ignored
+    let jwt = bundle.jwt_bundle();
+    assert_eq!(jwt.authorities().len(), 2);
+    let ec = PKey::public_key_from_pem(&fs::read(JWT_EC_KEY_PATH).unwrap()).unwrap();
+    assert!(jwt.find_authority("ec-key").unwrap().public_eq(&ec));
+    let rsa = PKey::public_key_from_pem(&fs::read(JWT_RSA_KEY_PATH).unwrap()).unwrap();
+    assert!(jwt.find_authority("rsa-key").unwrap().public_eq(&rsa));
+    assert!(!jwt.contains_authority("unknown-use"));
+}
+
+#[test]
+fn bundle_round_trip() {
+    let bundle = fixture();
+    let json = bundle.to_json().unwrap();
+    let parsed = SpiffeBundle::parse(trust_domain(), json.as_bytes()).unwrap();
+
+    assert_same_contents(&bundle, &parsed);
+    assert_eq!(parsed.to_json().unwrap(), json);
+}
+
+#[test]
+fn bundle_serializes_keys_as_parsed() {
+    let expected: Value = serde_json::from_slice(&fs::read(BUNDLE_PATH).unwrap()).unwrap();
+    let serialized: Value = serde_json::from_str(&fixture().to_json().unwrap()).unwrap();
+
+    // Everything but the key with an unknown use, whose members are written in the same form
+    let keys = expected["keys"].as_array().unwrap();
+    assert_eq!(serialized["keys"], Value::Array(keys[..3].to_vec()));
+    assert_eq!(serialized["spiffe_sequence"], expected["spiffe_sequence"]);
+    assert_eq!(
+        serialized["spiffe_refresh_hint"],
+        expected["spiffe_refresh_hint"]
+    );
+}
+
+#[test]
+fn empty_bundle_round_trip() {
+    let bundle = SpiffeBundle::new(trust_domain());
+    let json = bundle.to_json().unwrap();
+    assert_eq!(json, r#"{"keys":[]}"#);
+
+    let parsed = SpiffeBundle::parse(trust_domain(), json.as_bytes()).unwrap();
+    assert_same_contents(&bundle, &parsed);
+    assert_eq!(parsed.sequence(), None);
+    assert_eq!(parsed.refresh_hint(), None);
+}
+
+#[test]
+fn bundle_from_parts() {
+    let (x509, jwt) = fixture().into_bundles();
+    let mut bundle = SpiffeBundle::from_bundles(x509, jwt).unwrap();
+    bundle.set_sequence(Some(13));
+    bundle.set_refresh_hint(Some(Duration::from_secs(60)));
+    bundle.jwt_bundle_mut().remove_authority("rsa-key");
+
+    let parsed = SpiffeBundle::parse(trust_domain(), bundle.to_json().unwrap().as_bytes()).unwrap();
+    assert_same_contents(&bundle, &parsed);
+    assert_eq!(parsed.jwt_bundle().authorities().len(), 1);
+
+    let other = JwtBundle::new(TrustDomain::new("example.org").unwrap());
+    assert_matches!(
+        SpiffeBundle::from_bundles(X509Bundle::new(trust_domain()), other),
+        Err(Error(ErrorKind::TrustDomainMismatch(_, _), _))
+    );
+}
+
+#[test]
+fn parse_invalid_documents() {
+    let fixture: Value = serde_json::from_slice(&fs::read(BUNDLE_PATH).unwrap()).unwrap();
+    let x509 = fixture["keys"][0].clone();
+    let ec = fixture["keys"][1].clone();
+
+    let with = |key: &Value, member: &str, value: Value| -> Value {
+        let mut key = key.clone();
+        key[member] = value;
+        json!({ "keys": [key] })
+    };
+    let without = |key: &Value, member: &str| -> Value {
+        let mut key = key.clone();
+        key.as_object_mut().unwrap().remove(member);
+        json!({ "keys": [key] })
+    };
+
+    let invalid = vec![
+        json!([]),
+        json!({}),
+        json!({ "keys": {} }),
+        json!({ "keys": [42] }),
+        json!({ "keys": [], "spiffe_sequence": "12" }),
+        json!({ "keys": [], "spiffe_refresh_hint": -1 }),
+        without(&ec, "use"),
+        without(&ec, "kid"),
+        with(&ec, "kid", json!("")),
+        json!({ "keys": [ec.clone(), ec.clone()] }),
+        with(&ec, "kty", json!("oct")),
+        with(&ec, "crv", json!("secp256k1")),
+        with(&ec, "x", json!("not+base64url")),
+        with(&ec, "y", json!("AAAA")),
+        without(&x509, "x5c"),
+        with(&x509, "x5c", json!([])),
+        with(&x509, "x5c", json!([x509["x5c"][0], x509["x5c"][0]])),
+        with(&x509, "x5c", json!(["AAAA"])),
+    ];
+    for document in invalid.iter() {
+        assert_matches!(
+            parse(document),
+            Err(Error(ErrorKind::InvalidSpiffeBundle(_, _), _)),
+            "{}",
+            document
+        );
+    }
+
+    assert_matches!(
+        SpiffeBundle::parse(trust_domain(), b"{"),
+        Err(Error(ErrorKind::Json(_), _))
+    );
+}
@@ -0,0 +1,39 @@
+//! Kept in its own test binary so that no other test allocates while the counter is read.
+
+extern crate spiffe;
+
+use spiffe::uri::SpiffeIdRef;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn id_ref_parsing_does_not_allocate() {
+    let input = "spiffe://example.org/ns/prod/sa/api";
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let id = SpiffeIdRef::new(input).unwrap();
+    let (td, path) = (id.trust_domain_name(), id.path());
+    let after = ALLOCATIONS.load(Ordering::SeqCst);
+
+    assert_eq!(td, "example.org");
+    assert_eq!(path, "/ns/prod/sa/api");
+    assert_eq!(before, after);
+}
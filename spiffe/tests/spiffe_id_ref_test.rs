@@ -0,0 +1,65 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate spiffe;
+
+use spiffe::uri::{Error, ErrorKind, SpiffeIdRef, URI};
+use std::convert::TryFrom;
+
+#[test]
+fn id_ref_slices() {
+    let id = SpiffeIdRef::new("spiffe://example.org/ns/prod/sa/api").unwrap();
+    assert_eq!(id.as_str(), "spiffe://example.org/ns/prod/sa/api");
+    assert_eq!(id.trust_domain_name(), "example.org");
+    assert_eq!(id.path(), "/ns/prod/sa/api");
+    assert_eq!(id.trust_domain(), "example.org");
+}
+
+#[test]
+fn id_ref_slices_borrow_input() {
+    let input = String::from("spiffe://example.org/service");
+    let id = SpiffeIdRef::new(&input).unwrap();
+    assert!(std::ptr::eq(id.as_str(), input.as_str()));
+    assert_eq!(id.path().as_ptr(), input[20..].as_ptr());
+}
+
+#[test]
+fn id_ref_to_uri_and_back() {
+    let id = SpiffeIdRef::try_from("spiffe://example.org/service").unwrap();
+    let uri = id.to_uri();
+    assert_eq!(uri.as_str(), id.as_str());
+    assert_eq!(uri.trust_domain(), "example.org");
+    assert_eq!(uri.path(), "/service");
+    assert_eq!(uri.as_id_ref(), id);
+    assert_eq!(uri, id);
+    assert_eq!(id, uri);
+}
+
+#[test]
+fn id_ref_from_uri_borrows() {
+    let uri = "spiffe://example.org/service".parse::<URI>().unwrap();
+    let id = uri.as_id_ref();
+    assert!(std::ptr::eq(id.as_str(), uri.as_str()));
+}
+
+#[test]
+fn id_ref_display() {
+    let id = SpiffeIdRef::new("spiffe://example.org/service").unwrap();
+    assert_eq!(id.to_string(), "spiffe://example.org/service");
+}
+
+#[test]
+fn id_ref_uses_strict_grammar() {
+    assert_matches!(
+        SpiffeIdRef::new("spiffe://example.org/path/"),
+        Err(Error(ErrorKind::TrailingSlash(_), _))
+    );
+    assert_matches!(
+        SpiffeIdRef::new("spiffe://Example.org/path"),
+        Err(Error(ErrorKind::UppercaseTrustDomain(_), _))
+    );
+    assert_matches!(
+        SpiffeIdRef::new("spiffe://example.org"),
+        Err(Error(ErrorKind::MissingPath(_), _))
+    );
+}
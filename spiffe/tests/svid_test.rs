@@ -6,6 +6,7 @@ extern crate spiffe;
 
 use spiffe::svid::x509::{Error, ErrorKind, X509};
 use spiffe::svid::SVID;
+use std::fs;
 use std::path::Path;
 
 static GOOD_CERTIFICATE: &str = r#"
@@ -75,6 +76,7 @@ fDGmAkjHrspiorTruphHk+cymJfjAGqZ0l6il4Wi5w4R5jrxnJMhkxbjr/PG5xCS
 -----END CERTIFICATE-----"#;
 
 static LEAF_CERTIFICATE_PATH: &str = "./tests/leaf.cert.pem";
+static INTERMEDIATE_CERTIFICATE_PATH: &str = "./tests/intermediate.cert.pem";
 
 #[test]
 fn svid_from_pem() {
@@ -117,6 +119,47 @@ fn svid_from_invalid_pem_bad_pem() {
     }
 }
 
+#[test]
+fn svid_chain_from_pem() {
+    let mut pem = fs::read(LEAF_CERTIFICATE_PATH).unwrap();
+    pem.push(b'\n');
+    pem.extend(fs::read(INTERMEDIATE_CERTIFICATE_PATH).unwrap());
+
+    let svid = SVID::<X509>::from_pem(&pem, None, None).unwrap();
+    assert_eq!(svid.uri().to_string(), GOOD_CERTIFICATE_URI);
+    assert_eq!(svid.chain().len(), 2);
+    assert_eq!(svid.intermediates().len(), 1);
+    assert_eq!(
+        svid.intermediates()[0].to_der().unwrap(),
+        openssl::x509::X509::from_pem(&fs::read(INTERMEDIATE_CERTIFICATE_PATH).unwrap())
+            .unwrap()
+            .to_der()
+            .unwrap()
+    );
+}
+
+#[test]
+fn svid_chain_from_der() {
+    let leaf = SVID::<X509>::from_path(Path::new(LEAF_CERTIFICATE_PATH), None, None).unwrap();
+    let intermediate =
+        openssl::x509::X509::from_pem(&fs::read(INTERMEDIATE_CERTIFICATE_PATH).unwrap()).unwrap();
+
+    let mut der = leaf.leaf().to_der().unwrap();
+    der.extend(intermediate.to_der().unwrap());
+
+    let svid = SVID::<X509>::from_der(&der, None, None).unwrap();
+    assert_eq!(svid.leaf().to_der().unwrap(), leaf.leaf().to_der().unwrap());
+    assert_eq!(svid.intermediates().len(), 1);
+}
+
+#[test]
+fn svid_from_empty_der() {
+    assert_matches!(
+        SVID::<X509>::from_der(&[], None, None),
+        Err(Error(ErrorKind::EmptyChain, _))
+    );
+}
+
 #[test]
 fn svid_from_path() {
     assert!(SVID::<X509>::from_path(Path::new(LEAF_CERTIFICATE_PATH), None, None).is_ok());
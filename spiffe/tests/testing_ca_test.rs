@@ -0,0 +1,126 @@
+#![cfg(feature = "testing")]
+
+#[macro_use]
+extern crate assert_matches;
+
+extern crate spiffe;
+
+use spiffe::svid::x509::{validate_signing_certificate, Error, ErrorKind};
+use spiffe::testing::x509::{Quirk, SVIDTemplate, CA};
+use spiffe::testing::KeyType;
+use spiffe::uri::TrustDomain;
+use std::time::{Duration, SystemTime};
+
+const SPIFFE_ID: &str = "spiffe://example.org/workload";
+
+fn ca() -> CA {
+    CA::new(TrustDomain::new("example.org").unwrap()).unwrap()
+}
+
+#[test]
+fn issue_svid() {
+    let ca = ca();
+    let svid = ca.issue(SPIFFE_ID).unwrap();
+
+    assert_eq!(svid.uri().to_string(), SPIFFE_ID);
+    assert_eq!(svid.chain().len(), 1);
+    assert!(svid.key().is_some());
+    assert_eq!(svid.bundle(), Some(&ca.bundle_der().unwrap()));
+    assert_eq!(svid.verify(&ca.bundle()).unwrap().to_string(), SPIFFE_ID);
+}
+
+#[test]
+fn issue_svid_through_intermediates() {
+    let ca = CA::with_intermediates(TrustDomain::new("example.org").unwrap(), 2).unwrap();
+    let svid = ca.issue(SPIFFE_ID).unwrap();
+
+    assert_eq!(svid.chain().len(), 3);
+    assert_eq!(svid.intermediates(), &ca.intermediates()[..]);
+    assert_eq!(ca.bundle().authorities(), &[ca.root().clone()]);
+    for intermediate in ca.intermediates() {
+        assert_eq!(
+            validate_signing_certificate(&intermediate).unwrap(),
+            *ca.trust_domain()
+        );
+    }
+    assert_eq!(svid.verify(&ca.bundle()).unwrap().to_string(), SPIFFE_ID);
+}
+
+#[test]
+fn issue_svid_key_types() {
+    let ca = ca();
+    for key_type in &[KeyType::EcP256, KeyType::EcP384, KeyType::Rsa2048] {
+        let svid = ca
+            .issue_with(&SVIDTemplate::new(SPIFFE_ID).key_type(*key_type))
+            .unwrap();
+        assert!(svid.verify(&ca.bundle()).is_ok());
+    }
+}
+
+#[test]
+fn issue_svid_lifetimes() {
+    let ca = ca();
+    let hour = Duration::from_secs(60 * 60);
+
+    let expired = SVIDTemplate::new(SPIFFE_ID)
+        .not_before(SystemTime::now() - 2 * hour)
+        .lifetime(hour);
+    assert_matches!(
+        ca.issue_with(&expired).unwrap().verify(&ca.bundle()),
+        Err(Error(ErrorKind::CertificateExpired(0), _))
+    );
+
+    let future = SVIDTemplate::new(SPIFFE_ID).not_before(SystemTime::now() + hour);
+    assert_matches!(
+        ca.issue_with(&future).unwrap().verify(&ca.bundle()),
+        Err(Error(ErrorKind::CertificateNotYetValid(0), _))
+    );
+}
+
+#[test]
+fn issue_svid_quirks() {
+    let ca = ca();
+    let issue = |quirk: Quirk| ca.issue_with(&SVIDTemplate::new(SPIFFE_ID).quirk(quirk));
+
+    assert_matches!(issue(Quirk::CA), Err(Error(ErrorKind::LeafIsCA, _)));
+    assert_matches!(
+        issue(Quirk::NoDigitalSignature),
+        Err(Error(ErrorKind::LeafMissingDigitalSignature, _))
+    );
+    assert_matches!(
+        issue(Quirk::KeyCertSign),
+        Err(Error(ErrorKind::LeafHasKeyCertSign, _))
+    );
+    assert_matches!(
+        issue(Quirk::CRLSign),
+        Err(Error(ErrorKind::LeafHasCRLSign, _))
+    );
+    assert_matches!(issue(Quirk::NoSAN), Err(Error(ErrorKind::InvalidSAN, _)));
+    assert_matches!(
+        issue(Quirk::ExtraURI("spiffe://example.org/other".to_string())),
+        Err(Error(ErrorKind::MultipleURIFound(_, _), _))
+    );
+
+    // The certificates are still available to test other consumers with
+    let (chain, _) = ca
+        .issue_chain(&SVIDTemplate::new(SPIFFE_ID).quirk(Quirk::CA))
+        .unwrap();
+    assert_eq!(chain.len(), 1);
+}
+
+#[test]
+fn issue_invalid_spiffe_id() {
+    assert_matches!(
+        ca().issue("https://example.org/workload"),
+        Err(Error(ErrorKind::NonSpiffeURI(_), _))
+    );
+}
+
+#[test]
+fn verify_against_other_ca() {
+    let svid = ca().issue(SPIFFE_ID).unwrap();
+    assert_matches!(
+        svid.verify(&ca().bundle()),
+        Err(Error(ErrorKind::UnknownAuthority, _))
+    );
+}
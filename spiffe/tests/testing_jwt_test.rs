@@ -0,0 +1,184 @@
+#![cfg(feature = "testing")]
+
+#[macro_use]
+extern crate assert_matches;
+
+extern crate openssl;
+extern crate serde_json;
+extern crate spiffe;
+
+use openssl::base64;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{Id, PKey, Public};
+use openssl::sign::Verifier;
+use serde_json::{json, Value};
+use spiffe::bundle::spiffe::SpiffeBundle;
+use spiffe::svid::jwt::{Error, ErrorKind};
+use spiffe::testing::jwt::{Algorithm, JWTTemplate, Quirk, Signer};
+use spiffe::uri::TrustDomain;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SPIFFE_ID: &str = "spiffe://example.org/workload";
+
+fn signer() -> Signer {
+    let mut signer = Signer::new(TrustDomain::new("example.org").unwrap());
+    signer.generate_key("ec-key", Algorithm::ES256).unwrap();
+    signer.generate_key("rsa-key", Algorithm::RS256).unwrap();
+    signer
+}
+
+fn decode(part: &str) -> Vec<u8> {
+    let mut standard: String = part
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    let padding = (4 - standard.len() % 4) % 4;
+    standard.push_str(&"=="[..padding]);
+    base64::decode_block(&standard).unwrap()
+}
+
+fn parts(token: &str) -> (Value, Value, Vec<u8>) {
+    let parts: Vec<&str> = token.split('.').collect();
+    assert_eq!(parts.len(), 3);
+    (
+        serde_json::from_slice(&decode(parts[0])).unwrap(),
+        serde_json::from_slice(&decode(parts[1])).unwrap(),
+        decode(parts[2]),
+    )
+}
+
+fn verify(token: &str, key: &PKey<Public>) -> bool {
+    let (_, _, signature) = parts(token);
+    let input = &token[..token.rfind('.').unwrap()];
+
+    let signature = match key.id() {
+        Id::EC => {
+            let r = BigNum::from_slice(&signature[..32]).unwrap();
+            let s = BigNum::from_slice(&signature[32..]).unwrap();
+            EcdsaSig::from_private_components(r, s)
+                .unwrap()
+                .to_der()
+                .unwrap()
+        }
+        _ => signature,
+    };
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), key).unwrap();
+    verifier.update(input.as_bytes()).unwrap();
+    verifier.verify(&signature).unwrap_or(false)
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[test]
+fn sign_es256_and_rs256() {
+    let signer = signer();
+    let bundle = signer.bundle().unwrap();
+
+    for (key_id, alg) in &[("ec-key", "ES256"), ("rsa-key", "RS256")] {
+        let template = JWTTemplate::new(SPIFFE_ID, &["audience"]).key_id(key_id);
+        let token = signer.sign(&template).unwrap();
+
+        let (header, claims, _) = parts(&token);
+        assert_eq!(header["alg"], *alg);
+        assert_eq!(header["kid"], *key_id);
+        assert_eq!(header["typ"], "JWT");
+        assert_eq!(claims["sub"], SPIFFE_ID);
+        assert_eq!(claims["aud"], json!(["audience"]));
+        assert!(verify(&token, bundle.find_authority(key_id).unwrap()));
+    }
+}
+
+#[test]
+fn sign_claims() {
+    let issued_at = SystemTime::now() - Duration::from_secs(60);
+    let expiry = issued_at + Duration::from_secs(30);
+    let template = JWTTemplate::new(SPIFFE_ID, &["a", "b"])
+        .issued_at(issued_at)
+        .expiry(expiry)
+        .claim("group", json!("admins"))
+        .claim("sub", json!("not a SPIFFE ID"));
+
+    let (_, claims, _) = parts(&signer().sign(&template).unwrap());
+    assert_eq!(claims["aud"], json!(["a", "b"]));
+    assert_eq!(claims["iat"], unix_time(issued_at));
+    assert_eq!(claims["exp"], unix_time(expiry));
+    assert_eq!(claims["group"], "admins");
+    assert_eq!(claims["sub"], "not a SPIFFE ID");
+}
+
+#[test]
+fn sign_quirks() {
+    let signer = signer();
+    let bundle = signer.bundle().unwrap();
+    let template = || JWTTemplate::new(SPIFFE_ID, &["audience"]);
+
+    let (header, _, _) = parts(&signer.sign(&template().quirk(Quirk::NoKid)).unwrap());
+    assert!(header.get("kid").is_none());
+
+    let token = signer
+        .sign(&template().quirk(Quirk::Alg("none".to_string())))
+        .unwrap();
+    assert_eq!(parts(&token).0["alg"], "none");
+    assert!(verify(&token, bundle.find_authority("ec-key").unwrap()));
+
+    let (_, claims, _) = parts(&signer.sign(&template().quirk(Quirk::NoExp)).unwrap());
+    assert!(claims.get("exp").is_none());
+
+    let token = signer.sign(&template().quirk(Quirk::BadSignature)).unwrap();
+    assert!(!verify(&token, bundle.find_authority("ec-key").unwrap()));
+}
+
+#[test]
+fn sign_with_unknown_key() {
+    let template = JWTTemplate::new(SPIFFE_ID, &["audience"]).key_id("missing");
+    assert_matches!(
+        signer().sign(&template),
+        Err(Error(ErrorKind::UnknownKeyId(ref key_id), _)) if key_id == "missing"
+    );
+
+    let empty = Signer::new(TrustDomain::new("example.org").unwrap());
+    assert_matches!(
+        empty.sign(&JWTTemplate::new(SPIFFE_ID, &["audience"])),
+        Err(Error(ErrorKind::UnknownKeyId(_), _))
+    );
+}
+
+#[test]
+fn issue_svid() {
+    let signer = signer();
+    let template = JWTTemplate::new(SPIFFE_ID, &["audience"]);
+    let svid = signer.issue(&template).unwrap();
+    assert_eq!(svid.uri().to_string(), SPIFFE_ID);
+    assert_eq!(svid.header().kid(), Some("ec-key"));
+
+    assert_matches!(
+        signer.issue(&template.quirk(Quirk::NoExp)),
+        Err(Error(ErrorKind::MalformedToken(_), _))
+    );
+}
+
+#[test]
+fn publish_jwks() {
+    let mut signer = signer();
+    let trust_domain = signer.trust_domain().clone();
+
+    let bundle =
+        SpiffeBundle::parse(trust_domain.clone(), signer.jwks().unwrap().as_bytes()).unwrap();
+    assert!(bundle.x509_bundle().authorities().is_empty());
+    assert!(bundle.jwt_bundle().contains_authority("ec-key"));
+    assert!(bundle.jwt_bundle().contains_authority("rsa-key"));
+
+    assert!(signer.remove_key("rsa-key"));
+    assert!(!signer.remove_key("rsa-key"));
+    let bundle = SpiffeBundle::parse(trust_domain, signer.jwks().unwrap().as_bytes()).unwrap();
+    assert_eq!(bundle.jwt_bundle().authorities().len(), 1);
+}
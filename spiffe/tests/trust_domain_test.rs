@@ -0,0 +1,112 @@
+#[macro_use]
+extern crate assert_matches;
+
+extern crate spiffe;
+
+use spiffe::uri::{Error, ErrorKind, TrustDomain};
+use std::collections::HashSet;
+
+#[test]
+fn trust_domain_from_name() {
+    let td = "example.org".parse::<TrustDomain>().unwrap();
+    assert_eq!(td.name(), "example.org");
+    assert_eq!(td.to_string(), "example.org");
+    assert_eq!(td.id_string(), "spiffe://example.org");
+}
+
+#[test]
+fn trust_domain_from_spiffe_id() {
+    let td = "spiffe://example.org".parse::<TrustDomain>().unwrap();
+    assert_eq!(td, "example.org");
+}
+
+#[test]
+fn trust_domain_allowed_characters() {
+    TrustDomain::new("a-b_c.0123456789").unwrap();
+}
+
+#[test]
+fn trust_domain_max_length() {
+    TrustDomain::new(&"a".repeat(255)).unwrap();
+}
+
+#[test]
+fn trust_domain_equality_and_hash() {
+    let mut set = HashSet::new();
+    set.insert(TrustDomain::new("example.org").unwrap());
+    assert!(set.contains(&TrustDomain::new("spiffe://example.org").unwrap()));
+    assert!(!set.contains(&TrustDomain::new("example.com").unwrap()));
+}
+
+#[test]
+fn trust_domain_ordering() {
+    let mut tds = [
+        TrustDomain::new("b.org").unwrap(),
+        TrustDomain::new("a.org").unwrap(),
+    ];
+    tds.sort();
+    assert_eq!(tds[0], "a.org");
+}
+
+#[test]
+fn invalid_trust_domain_empty() {
+    assert_matches!(
+        TrustDomain::new(""),
+        Err(Error(ErrorKind::MissingTrustDomain, _))
+    );
+    assert_matches!(
+        TrustDomain::new("spiffe://"),
+        Err(Error(ErrorKind::MissingTrustDomain, _))
+    );
+}
+
+#[test]
+fn invalid_trust_domain_too_long() {
+    assert_matches!(
+        TrustDomain::new(&"a".repeat(256)),
+        Err(Error(ErrorKind::TrustDomainTooLong(256), _))
+    );
+}
+
+#[test]
+fn invalid_trust_domain_uppercase() {
+    assert_matches!(
+        TrustDomain::new("Example.org"),
+        Err(Error(ErrorKind::UppercaseTrustDomain(_), _))
+    );
+}
+
+#[test]
+fn invalid_trust_domain_characters() {
+    for name in &[
+        "exa^mple.org",
+        "example.org:8080",
+        "user@example.org",
+        "example✔",
+    ] {
+        assert_matches!(
+            TrustDomain::new(name),
+            Err(Error(ErrorKind::BadTrustDomainChar(_), _))
+        );
+    }
+}
+
+#[test]
+fn invalid_trust_domain_with_path() {
+    assert_matches!(
+        TrustDomain::new("spiffe://example.org/path"),
+        Err(Error(ErrorKind::TrustDomainHasPath(ref id), _)) if id == "spiffe://example.org/path"
+    );
+    assert_matches!(
+        TrustDomain::new("spiffe://example.org/"),
+        Err(Error(ErrorKind::TrustDomainHasPath(_), _))
+    );
+}
+
+#[test]
+fn invalid_trust_domain_wrong_scheme() {
+    assert_matches!(
+        TrustDomain::new("https://example.org"),
+        Err(Error(ErrorKind::WrongScheme(_), _))
+    );
+}